@@ -9,6 +9,8 @@ fn main() {
         .with_std_types(true)       // Emits uint32_t, int32_t etc. from <stdint.h>
         .with_documentation(true)   // Includes doc comments as C comments
         .with_sys_include("stdint.h")
+        // Feature-gated functions are wrapped in #if defined(...) in the header
+        .with_define("feature", "labview", "HTTP_RS_LABVIEW_FEATURE")
         .generate()
         .expect("Unable to generate C bindings")
         .write_to_file(output_file);
@@ -0,0 +1,20 @@
+/// Upper bound of the backoff window for `attempt` (0-based):
+/// min(max_ms, base_ms * 2^attempt), saturating instead of overflowing.
+pub fn ceiling_ms(attempt: u32, base_ms: u64, max_ms: u64) -> u64 {
+    let factor = 1u64.checked_shl(attempt.min(63)).unwrap_or(u64::MAX);
+    base_ms.saturating_mul(factor).min(max_ms)
+}
+
+/// Full-jitter exponential backoff: a uniformly random delay in
+/// [0, ceiling_ms(attempt, base_ms, max_ms)].
+///
+/// Uses the calling thread's RNG, which can be seeded with `seed` to make a
+/// sequence of delays reproducible.
+pub fn full_jitter_ms(attempt: u32, base_ms: u64, max_ms: u64) -> u64 {
+    fastrand::u64(0..=ceiling_ms(attempt, base_ms, max_ms))
+}
+
+/// Seed the calling thread's jitter RNG.
+pub fn seed(seed: u64) {
+    fastrand::seed(seed);
+}
@@ -0,0 +1,52 @@
+use crate::error::{set_last_error, ERR_BUFFER_TOO_SMALL, ERR_NULL_PTR};
+
+/// Copy bytes into a caller-supplied buffer without a terminator.
+/// Returns the number of bytes written, or ERR_BUFFER_TOO_SMALL (with the
+/// required size in the error string) if they do not all fit.
+pub fn write_bytes(bytes: &[u8], buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    if buf_ptr.is_null() {
+        set_last_error(ERR_NULL_PTR, "Output buffer pointer is null");
+        return ERR_NULL_PTR;
+    }
+    let available = buf_len.max(0) as usize;
+    if bytes.len() > available {
+        set_last_error(
+            ERR_BUFFER_TOO_SMALL,
+            format!(
+                "Buffer too small: need {} bytes, got {}",
+                bytes.len(),
+                available
+            ),
+        );
+        return ERR_BUFFER_TOO_SMALL;
+    }
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf_ptr, bytes.len());
+    }
+    bytes.len() as i32
+}
+
+/// Copy a string into a caller-supplied buffer followed by a null terminator,
+/// so it can be wired to a LabVIEW "C String Pointer" like http_get_last_error.
+/// Returns the string length (excluding the terminator), or ERR_BUFFER_TOO_SMALL
+/// if the buffer cannot hold the string plus terminator.
+pub fn write_c_string(s: &str, buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    if buf_ptr.is_null() {
+        set_last_error(ERR_NULL_PTR, "Output buffer pointer is null");
+        return ERR_NULL_PTR;
+    }
+    let needed = s.len() + 1;
+    let available = buf_len.max(0) as usize;
+    if needed > available {
+        set_last_error(
+            ERR_BUFFER_TOO_SMALL,
+            format!("Buffer too small: need {} bytes, got {}", needed, available),
+        );
+        return ERR_BUFFER_TOO_SMALL;
+    }
+    unsafe {
+        std::ptr::copy_nonoverlapping(s.as_ptr(), buf_ptr, s.len());
+        *buf_ptr.add(s.len()) = 0;
+    }
+    s.len() as i32
+}
@@ -0,0 +1,415 @@
+// Cancellation tokens: one token can be shared by any number of requests,
+// and tripping it aborts every request running with it.
+//
+// A request with a token runs on the async client as tasks on the runtime in
+// tasks: one until the response headers arrive, then one that pumps the body
+// to the caller. Tripping the token aborts both, which drops the request and
+// closes its connection, so the caller returns ERR_CANCELLED at once.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use reqwest::blocking::{Request, Response};
+use reqwest::ResponseBuilderExt;
+use tokio::task::AbortHandle;
+
+use crate::error::{set_last_error, ERR_CANCELLED, ERR_INVALID_HANDLE, ERR_TIMEOUT};
+use crate::runtime::get_async_client;
+use crate::tasks;
+
+/// The blocking client's default, which it applies to the wait for the
+/// response headers and to each read of the body when a request sets no
+/// timeout of its own. Cancellable requests keep the same limits.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Body chunks buffered between the network and the reader.
+const BODY_CHUNKS: usize = 16;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Default)]
+struct Token {
+    state: Mutex<TokenState>,
+}
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: bool,
+    /// Tasks of the requests using the token.
+    tasks: Vec<AbortHandle>,
+}
+
+impl Token {
+    fn trip(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.cancelled = true;
+        for task in state.tasks.drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Abort `task` when the token is tripped, or now if it already was.
+    fn bind(&self, task: &AbortHandle) {
+        let mut state = self.state.lock().unwrap();
+        if state.cancelled {
+            task.abort();
+        } else {
+            state.tasks.retain(|t| !t.is_finished());
+            state.tasks.push(task.clone());
+        }
+    }
+}
+
+/// The error a cancelled request's body read fails with.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Request was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+static TOKENS: OnceLock<Mutex<HashMap<u64, Arc<Token>>>> = OnceLock::new();
+
+// Starts at 1 so that 0 can serve as a sentinel "no token" value in LabVIEW
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+// Token applied to every request that is not given one; 0 for none.
+static DEFAULT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Token that also applies to requests made on this thread; see scoped.
+    static SCOPED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+fn tokens() -> &'static Mutex<HashMap<u64, Arc<Token>>> {
+    TOKENS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn lookup(token: u64) -> Result<Arc<Token>, i32> {
+    tokens()
+        .lock()
+        .unwrap()
+        .get(&token)
+        .cloned()
+        .ok_or_else(|| {
+            set_last_error(
+                ERR_INVALID_HANDLE,
+                format!("Invalid or already-freed cancel token: {}", token),
+            );
+            ERR_INVALID_HANDLE
+        })
+}
+
+fn cancelled() -> i32 {
+    set_last_error(ERR_CANCELLED, "Request was cancelled");
+    ERR_CANCELLED
+}
+
+/// Register a new, untripped token.
+pub fn new_token() -> u64 {
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    tokens().lock().unwrap().insert(token, Arc::default());
+    token
+}
+
+/// Trip `token`, aborting every request using it. It stays tripped.
+pub fn cancel(token: u64) -> Result<(), i32> {
+    lookup(token)?.trip();
+    Ok(())
+}
+
+/// Forget `token`. Requests already using it are unaffected. Stops it being
+/// the default token if it was.
+pub fn free(token: u64) -> Result<(), i32> {
+    match tokens().lock().unwrap().remove(&token) {
+        Some(_) => {
+            let _ = DEFAULT_TOKEN.compare_exchange(token, 0, Ordering::SeqCst, Ordering::SeqCst);
+            Ok(())
+        }
+        None => {
+            set_last_error(
+                ERR_INVALID_HANDLE,
+                format!("Invalid or already-freed cancel token: {}", token),
+            );
+            Err(ERR_INVALID_HANDLE)
+        }
+    }
+}
+
+/// Trip and forget every token. Called from http_shutdown_threads so no
+/// caller is left waiting on a request.
+pub fn cancel_all() {
+    DEFAULT_TOKEN.store(0, Ordering::SeqCst);
+    for (_, token) in tokens().lock().unwrap().drain() {
+        token.trip();
+    }
+}
+
+/// Run `f` with `token` applying to every request it makes on this thread,
+/// as well as any token those requests are given. Used for requests that
+/// are cancelled by handle rather than by a caller's token.
+pub fn scoped<T>(token: u64, f: impl FnOnce() -> T) -> T {
+    let outer = SCOPED.with(|scoped| scoped.replace(Some(token)));
+    let result = f();
+    SCOPED.with(|scoped| scoped.set(outer));
+    result
+}
+
+/// Make `token` apply to every request not given a token of its own; 0
+/// clears it.
+pub fn set_default(token: u64) -> Result<(), i32> {
+    if token != 0 {
+        lookup(token)?;
+    }
+    DEFAULT_TOKEN.store(token, Ordering::SeqCst);
+    Ok(())
+}
+
+/// The tokens a request given `token` (if any) runs with: that or else the
+/// default token, plus any scoped one.
+pub fn active(token: Option<u64>) -> Vec<u64> {
+    let default = Some(DEFAULT_TOKEN.load(Ordering::SeqCst)).filter(|&t| t != 0);
+    token.or(default).into_iter().chain(SCOPED.with(Cell::get)).collect()
+}
+
+/// Spawn `task` and bind it to `tokens`.
+fn spawn_bound(
+    tokens: &[Arc<Token>],
+    task: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), i32> {
+    let task = tasks::spawn(task)?;
+    for token in tokens {
+        token.bind(&task);
+    }
+    Ok(())
+}
+
+/// Run `task` to completion, or fail with ERR_CANCELLED as soon as one of
+/// `tokens` is tripped. A token that is already tripped fails without
+/// starting it.
+fn run<T: Send + 'static>(
+    tokens: &[Arc<Token>],
+    task: impl Future<Output = T> + Send + 'static,
+) -> Result<T, i32> {
+    if tokens.iter().any(|t| t.state.lock().unwrap().cancelled) {
+        return Err(cancelled());
+    }
+    let (done, result) = mpsc::channel();
+    spawn_bound(tokens, async move {
+        let _ = done.send(task.await);
+    })?;
+    // The sender is dropped unsent when the task is aborted.
+    result.recv().map_err(|_| cancelled())
+}
+
+/// Fail with ERR_CANCELLED if one of `tokens` has been tripped, for requests
+/// that have to be sent on the blocking client (see can_execute).
+pub fn check(tokens: &[u64]) -> Result<(), i32> {
+    for &token in tokens {
+        if lookup(token)?.state.lock().unwrap().cancelled {
+            return Err(cancelled());
+        }
+    }
+    Ok(())
+}
+
+/// Wait for `delay`, failing with ERR_CANCELLED if one of `tokens` is
+/// tripped first.
+pub fn sleep(tokens: &[u64], delay: Duration) -> Result<(), i32> {
+    let tokens = tokens.iter().map(|&t| lookup(t)).collect::<Result<Vec<_>, _>>()?;
+    run(&tokens, tokio::time::sleep(delay))
+}
+
+/// Whether `request` can be sent by `execute`: its body, if any, is in
+/// memory, or `chunked`, meaning it is an in-memory reader to be sent with
+/// chunked encoding. Bodies streamed from elsewhere (files) cannot be.
+pub fn can_execute(request: &Request, chunked: bool) -> bool {
+    chunked || request.body().is_none_or(|b| b.as_bytes().is_some())
+}
+
+/// Send `request` on the async client, as the blocking client would, and
+/// return once the response headers arrive; the body is read as it is
+/// consumed. Fails with ERR_CANCELLED if one of `tokens` is tripped first, and
+/// later reads of the body fail with a Cancelled error if one is tripped
+/// before the body ends. The request must pass can_execute.
+pub fn execute(tokens: &[u64], request: Request) -> Result<reqwest::Result<Response>, i32> {
+    let tokens = tokens.iter().map(|&t| lookup(t)).collect::<Result<Vec<_>, _>>()?;
+    let client = get_async_client()?;
+    let request = match into_async(request) {
+        Ok(request) => request,
+        Err(e) => return Ok(Err(e)),
+    };
+    let url = request.url().clone();
+    let timeout = request.timeout().copied();
+
+    let head = run(&tokens, async move {
+        match timeout {
+            Some(_) => Some(client.execute(request).await),
+            None => tokio::time::timeout(DEFAULT_TIMEOUT, client.execute(request)).await.ok(),
+        }
+    })?;
+    let response = match head {
+        Some(Ok(response)) => response,
+        Some(Err(e)) => return Ok(Err(e)),
+        None => {
+            set_last_error(
+                ERR_TIMEOUT,
+                format!(
+                    "Request failed: no response from {} within {} ms",
+                    url,
+                    DEFAULT_TIMEOUT.as_millis()
+                ),
+            );
+            return Err(ERR_TIMEOUT);
+        }
+    };
+
+    let (chunks, receiver) = tokio::sync::mpsc::channel(BODY_CHUNKS);
+    let size = response.content_length();
+    let mut head = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = head.headers_mut() {
+        headers.extend(response.headers().clone());
+    }
+    let mut response = response;
+    if let Some(extensions) = head.extensions_mut() {
+        extensions.extend(std::mem::take(response.extensions_mut()));
+    }
+    let body = reqwest::Body::wrap(ChannelBody {
+        receiver,
+        size,
+        ended: false,
+    });
+    let head = head.body(body).expect("parts of a valid response");
+
+    // The async client's own timeout covers the body too, so only a request
+    // without one needs the blocking client's per-read limit.
+    let idle = timeout.is_none().then_some(DEFAULT_TIMEOUT);
+    spawn_bound(&tokens, pump(response, chunks, idle))?;
+    Ok(Ok(Response::from(head)))
+}
+
+/// A blocking request as the equivalent async one.
+fn into_async(mut request: Request) -> reqwest::Result<reqwest::Request> {
+    let mut converted = reqwest::Request::new(request.method().clone(), request.url().clone());
+    *converted.headers_mut() = std::mem::take(request.headers_mut());
+    *converted.version_mut() = request.version();
+    *converted.timeout_mut() = request.timeout().copied();
+    if let Some(body) = request.body_mut() {
+        let sized = body.as_bytes().is_some();
+        let bytes = Bytes::copy_from_slice(body.buffer()?);
+        *converted.body_mut() = Some(match sized {
+            true => bytes.into(),
+            // No size hint, so hyper sends it chunked.
+            false => reqwest::Body::wrap(Unsized(Some(bytes))),
+        });
+    }
+    Ok(converted)
+}
+
+/// Feed the body of `response` to `chunks` until it ends, fails or nobody
+/// is reading any more. `idle` limits the wait for each chunk.
+async fn pump(
+    mut response: reqwest::Response,
+    chunks: tokio::sync::mpsc::Sender<Result<Option<Bytes>, BoxError>>,
+    idle: Option<Duration>,
+) {
+    loop {
+        let chunk = match idle {
+            Some(idle) => match tokio::time::timeout(idle, response.chunk()).await {
+                Ok(chunk) => chunk.map_err(BoxError::from),
+                Err(_) => Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "timed out reading response body",
+                )
+                .into()),
+            },
+            None => response.chunk().await.map_err(BoxError::from),
+        };
+        let last = !matches!(chunk, Ok(Some(_)));
+        if chunks.send(chunk).await.is_err() || last {
+            return;
+        }
+    }
+}
+
+/// A response body read from pump. Ending without the end-of-body marker
+/// means the pump was aborted.
+struct ChannelBody {
+    receiver: tokio::sync::mpsc::Receiver<Result<Option<Bytes>, BoxError>>,
+    size: Option<u64>,
+    ended: bool,
+}
+
+impl http_body::Body for ChannelBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+        let chunk = match self.receiver.poll_recv(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(chunk) => chunk,
+        };
+        match chunk {
+            Some(Ok(Some(bytes))) => Poll::Ready(Some(Ok(Frame::data(bytes)))),
+            Some(Ok(None)) => {
+                self.ended = true;
+                Poll::Ready(None)
+            }
+            Some(Err(e)) => {
+                self.ended = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            None => {
+                self.ended = true;
+                Poll::Ready(Some(Err(Cancelled.into())))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.ended
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.size {
+            Some(size) => SizeHint::with_exact(size),
+            None => SizeHint::default(),
+        }
+    }
+}
+
+/// A request body sent in one piece but with no declared length.
+struct Unsized(Option<Bytes>);
+
+impl http_body::Body for Unsized {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Poll::Ready(self.0.take().map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
@@ -0,0 +1,120 @@
+// Text encodings for legacy endpoints. Only the handful of charsets we meet
+// in practice are supported; anything else is reported as unknown.
+
+/// windows-1252 code points for bytes 0x80..=0x9F. The five bytes the code page
+/// leaves undefined map to the matching C1 control, as browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Ascii,
+    Latin1,
+    Windows1252,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Charset {
+    /// Look up a charset by its IANA name or a common alias (case-insensitive).
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label.trim().trim_matches('"').to_ascii_lowercase();
+        match label.as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "us-ascii" | "ascii" => Some(Self::Ascii),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" | "l1" => Some(Self::Latin1),
+            "windows-1252" | "cp1252" | "x-cp1252" => Some(Self::Windows1252),
+            "utf-16le" => Some(Self::Utf16Le),
+            "utf-16be" => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Canonical name, as used in a Content-Type charset parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Ascii => "us-ascii",
+            Self::Latin1 => "iso-8859-1",
+            Self::Windows1252 => "windows-1252",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+        }
+    }
+
+    /// Encode UTF-8 text into this charset.
+    /// Returns the first character that has no representation as the error.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Utf16Le => Ok(text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()),
+            Self::Utf16Be => Ok(text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect()),
+            Self::Ascii => text
+                .chars()
+                .map(|c| if c.is_ascii() { Ok(c as u8) } else { Err(c) })
+                .collect(),
+            Self::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c as u32).map_err(|_| c))
+                .collect(),
+            Self::Windows1252 => text
+                .chars()
+                .map(|c| match c as u32 {
+                    0x00..=0x7F | 0xA0..=0xFF => Ok(c as u8),
+                    _ => WINDOWS_1252_HIGH
+                        .iter()
+                        .position(|&h| h == c)
+                        .map(|i| 0x80 + i as u8)
+                        .ok_or(c),
+                })
+                .collect(),
+        }
+    }
+
+    /// Decode bytes in this charset to UTF-8. Invalid sequences (and, for
+    /// US-ASCII, bytes above 0x7F) become U+FFFD.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Ascii => bytes
+                .iter()
+                .map(|&b| if b.is_ascii() { b as char } else { char::REPLACEMENT_CHARACTER })
+                .collect(),
+            Self::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+            Self::Utf16Le | Self::Utf16Be => {
+                let units = bytes.chunks(2).map(|pair| match (pair, self) {
+                    ([lo, hi], Self::Utf16Le) => u16::from_le_bytes([*lo, *hi]),
+                    ([hi, lo], _) => u16::from_be_bytes([*hi, *lo]),
+                    // A dangling odd byte is not a code unit; 0xFFFD stands in.
+                    _ => 0xFFFD,
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The charset parameter of a Content-Type value such as
+/// `text/plain; charset="ISO-8859-1"`, if present.
+pub fn content_type_charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
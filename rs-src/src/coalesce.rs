@@ -0,0 +1,112 @@
+// Single-flight request coalescing.
+//
+// The first caller for a key (the leader) performs the request; callers that
+// arrive with the same key while it is running wait on the flight and get a
+// copy of the leader's result.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use reqwest::blocking::Request;
+use ring::digest::{Context, SHA256};
+
+use crate::error::{last_error, set_last_error};
+use crate::http::HttpResponse;
+
+/// SHA-256 over the parts of a request that make it identical to another.
+pub type Key = [u8; 32];
+
+type Outcome = Result<HttpResponse, (i32, String)>;
+
+#[derive(Default)]
+struct Flight {
+    outcome: Mutex<Option<Outcome>>,
+    done: Condvar,
+}
+
+static FLIGHTS: OnceLock<Mutex<HashMap<Key, Arc<Flight>>>> = OnceLock::new();
+
+fn flights() -> &'static Mutex<HashMap<Key, Arc<Flight>>> {
+    FLIGHTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Derive the coalescing key: method, URL, headers sorted by name (values in
+/// their original order) and body. Returns None for streaming bodies, which
+/// cannot be inspected.
+pub fn key(request: &Request) -> Option<Key> {
+    let body = match request.body() {
+        Some(body) => body.as_bytes()?,
+        None => &[],
+    };
+
+    let mut names: Vec<_> = request.headers().keys().collect();
+    names.sort_by_key(|name| name.as_str());
+
+    let mut ctx = Context::new(&SHA256);
+    ctx.update(request.method().as_str().as_bytes());
+    ctx.update(b"\0");
+    ctx.update(request.url().as_str().as_bytes());
+    ctx.update(b"\0");
+    for name in names {
+        for value in request.headers().get_all(name) {
+            ctx.update(name.as_str().as_bytes());
+            ctx.update(b":");
+            ctx.update(value.as_bytes());
+            ctx.update(b"\n");
+        }
+    }
+    ctx.update(b"\0");
+    ctx.update(body);
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(ctx.finish().as_ref());
+    Some(key)
+}
+
+/// Run `perform` as the leader for `key`, or wait for the leader already
+/// running it and return a copy of its result.
+pub fn run(
+    key: Key,
+    perform: impl FnOnce() -> Result<HttpResponse, i32>,
+) -> Result<HttpResponse, i32> {
+    let (flight, leader) = {
+        let mut map = flights().lock().unwrap();
+        match map.get(&key) {
+            Some(flight) => (flight.clone(), false),
+            None => {
+                let flight = Arc::new(Flight::default());
+                map.insert(key, flight.clone());
+                (flight, true)
+            }
+        }
+    };
+
+    if !leader {
+        let mut outcome = flight.outcome.lock().unwrap();
+        while outcome.is_none() {
+            outcome = flight.done.wait(outcome).unwrap();
+        }
+        return match outcome.as_ref().unwrap() {
+            Ok(response) => Ok(response.clone()),
+            Err((code, message)) => {
+                set_last_error(*code, message.clone());
+                Err(*code)
+            }
+        };
+    }
+
+    let result = perform();
+    // Unregister first so callers arriving from now on start a fresh request.
+    flights().lock().unwrap().remove(&key);
+    // Nobody else holds the flight, so nobody is waiting and there is nothing
+    // to copy.
+    if Arc::strong_count(&flight) == 1 {
+        return result;
+    }
+    *flight.outcome.lock().unwrap() = Some(match &result {
+        Ok(response) => Ok(response.clone()),
+        Err(code) => Err((*code, last_error())),
+    });
+    flight.done.notify_all();
+    result
+}
@@ -0,0 +1,574 @@
+use reqwest::ClientBuilder;
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use std::borrow::Cow;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::dns;
+use crate::error::{
+    set_last_error, ERR_CLIENT_INIT, ERR_INVALID_ARGUMENT, ERR_REQUEST_TOO_LARGE,
+};
+use crate::timing::TimingLayer;
+use crate::tls::{
+    hostname_only_tls_config, load_client_key, load_identity, load_root_certificates,
+};
+
+/// How strictly server certificates are checked.
+/// Anything other than `Full` is REDUCED SECURITY and meant for test benches only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CertVerification {
+    /// Validate the chain against the trust store and check the hostname (default).
+    #[default]
+    Full,
+    /// Validate the chain but accept a certificate issued for a different name.
+    SkipHostname,
+    /// Check the hostname but accept an untrusted or self-signed chain.
+    SkipChain,
+    /// Accept any certificate.
+    None,
+}
+
+impl CertVerification {
+    /// Map the integer mode used over FFI: 0 full, 1 skip-hostname, 2 skip-chain, 3 none.
+    pub fn from_mode(mode: i32) -> Option<Self> {
+        match mode {
+            0 => Some(Self::Full),
+            1 => Some(Self::SkipHostname),
+            2 => Some(Self::SkipChain),
+            3 => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    /// The mode for reqwest-style flags: accepting invalid certificates skips
+    /// the chain check, accepting invalid hostnames the name check.
+    pub fn from_flags(accept_invalid_certs: bool, accept_invalid_hostnames: bool) -> Self {
+        match (accept_invalid_certs, accept_invalid_hostnames) {
+            (false, false) => Self::Full,
+            (false, true) => Self::SkipHostname,
+            (true, false) => Self::SkipChain,
+            (true, true) => Self::None,
+        }
+    }
+
+    pub fn accepts_invalid_certs(self) -> bool {
+        matches!(self, Self::SkipChain | Self::None)
+    }
+
+    pub fn accepts_invalid_hostnames(self) -> bool {
+        matches!(self, Self::SkipHostname | Self::None)
+    }
+
+    /// The warning to give when this mode is selected, None for Full.
+    pub fn warning(self) -> Option<String> {
+        (self != Self::Full).then(|| {
+            format!(
+                "WARNING: certificate verification reduced to '{}'; connections can be \
+                 intercepted. Use only on isolated test networks",
+                self.name()
+            )
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::SkipHostname => "skip_hostname",
+            Self::SkipChain => "skip_chain",
+            Self::None => "none",
+        }
+    }
+}
+
+/// A TLS protocol version the client may be limited to. Older versions are
+/// never offered: rustls does not implement them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Parse the "1.2" / "1.3" form used in http_init.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1.2" => Some(Self::Tls12),
+            "1.3" => Some(Self::Tls13),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tls12 => "1.2",
+            Self::Tls13 => "1.3",
+        }
+    }
+
+    fn reqwest(self) -> reqwest::tls::Version {
+        match self {
+            Self::Tls12 => reqwest::tls::Version::TLS_1_2,
+            Self::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
+
+/// Settings applied when the shared client is built.
+/// Changing them has no effect once the client exists.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    pub cert_verification: CertVerification,
+    /// Set a Referer header automatically when following redirects (reqwest default: on).
+    pub auto_referer: bool,
+    /// Idle time in milliseconds before OS-level TCP keepalive probes start;
+    /// 0 disables them.
+    pub tcp_keepalive_ms: u64,
+    /// User-Agent sent with every request (reqwest default: none).
+    pub user_agent: Option<String>,
+    /// Headers added to every request unless the request sets them itself.
+    pub default_headers: HeaderMap,
+    /// Most idle connections kept per host (reqwest default: unlimited).
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle pooled connection is kept; Some(0) keeps it forever
+    /// (reqwest default: 90 s).
+    pub pool_idle_timeout_ms: Option<u64>,
+    /// Disable Nagle's algorithm (reqwest default: on).
+    pub tcp_nodelay: bool,
+    /// Open a new connection for every request: none are kept idle and TCP
+    /// keepalive is off, whatever the pool and keepalive settings say.
+    pub disable_keepalive: bool,
+    /// Speak only HTTP/1.x rather than negotiating the version (reqwest
+    /// default: negotiate).
+    pub http1_only: bool,
+    /// PEM file of extra root certificates to trust, e.g. a private CA.
+    pub ca_cert_path: Option<PathBuf>,
+    /// PEM certificate chain and private key presented for mutual TLS.
+    pub client_identity: Option<(PathBuf, PathBuf)>,
+    /// Proxy for http:// URLs; credentials may be given in the URL.
+    pub http_proxy: Option<String>,
+    /// Proxy for https:// URLs, likewise.
+    pub https_proxy: Option<String>,
+    /// Hosts, domains and IP ranges (comma-separated) that bypass the proxies.
+    pub no_proxy: Option<String>,
+    /// Ignore HTTP_PROXY and friends in the environment (reqwest default:
+    /// honoured unless a proxy is set explicitly).
+    pub no_proxy_from_env: bool,
+    /// Hosts resolved to fixed addresses instead of through DNS, for every
+    /// port.
+    pub dns_overrides: Vec<(String, Vec<IpAddr>)>,
+    /// Oldest TLS version accepted (default: 1.2, the oldest rustls speaks).
+    pub tls_min_version: Option<TlsVersion>,
+    /// Newest TLS version offered (default: 1.3).
+    pub tls_max_version: Option<TlsVersion>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            cert_verification: CertVerification::Full,
+            auto_referer: true,
+            tcp_keepalive_ms: 30_000,
+            user_agent: None,
+            default_headers: HeaderMap::new(),
+            pool_max_idle_per_host: None,
+            pool_idle_timeout_ms: None,
+            tcp_nodelay: true,
+            disable_keepalive: false,
+            http1_only: false,
+            ca_cert_path: None,
+            client_identity: None,
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            no_proxy_from_env: false,
+            dns_overrides: Vec::new(),
+            tls_min_version: None,
+            tls_max_version: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Apply these settings to a client builder.
+    pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, i32> {
+        let (min_tls, max_tls) = (
+            self.tls_min_version.unwrap_or(TlsVersion::Tls12),
+            self.tls_max_version.unwrap_or(TlsVersion::Tls13),
+        );
+        if min_tls > max_tls {
+            set_last_error(
+                ERR_CLIENT_INIT,
+                format!(
+                    "tls_min_version {} is newer than tls_max_version {}",
+                    min_tls.name(),
+                    max_tls.name()
+                ),
+            );
+            return Err(ERR_CLIENT_INIT);
+        }
+        let builder = builder
+            .min_tls_version(min_tls.reqwest())
+            .max_tls_version(max_tls.reqwest());
+        let builder = match self.cert_verification {
+            CertVerification::Full => builder,
+            CertVerification::SkipHostname => builder.danger_accept_invalid_hostnames(true),
+            CertVerification::SkipChain => {
+                // A preconfigured TLS setup bypasses the builder's version
+                // limits and identity, so they go into it directly.
+                let client_key = match &self.client_identity {
+                    Some((cert_path, key_path)) => {
+                        Some(load_client_key(cert_path, key_path).map_err(|e| {
+                            set_last_error(ERR_CLIENT_INIT, e);
+                            ERR_CLIENT_INIT
+                        })?)
+                    }
+                    None => None,
+                };
+                let tls = hostname_only_tls_config(min_tls, max_tls, client_key).map_err(|e| {
+                    set_last_error(
+                        ERR_CLIENT_INIT,
+                        format!("Failed to build TLS configuration: {}", e),
+                    );
+                    ERR_CLIENT_INIT
+                })?;
+                builder.use_preconfigured_tls(tls)
+            }
+            CertVerification::None => builder.danger_accept_invalid_certs(true),
+        };
+        let builder = builder.referer(self.auto_referer);
+        // The OS counts keepalive idle time in whole seconds, so round up
+        // rather than let a sub-second value become 0.
+        let keepalive_secs = self.tcp_keepalive_ms.div_ceil(1000);
+        let keepalive = (keepalive_secs > 0).then(|| Duration::from_secs(keepalive_secs));
+        let builder = builder.tcp_keepalive(keepalive);
+        let builder = match &self.user_agent {
+            Some(agent) => builder.user_agent(agent.as_str()),
+            None => builder,
+        };
+        let builder = builder.default_headers(self.default_headers.clone());
+        let builder = match self.pool_max_idle_per_host {
+            Some(max) => builder.pool_max_idle_per_host(max),
+            None => builder,
+        };
+        let builder = match self.pool_idle_timeout_ms {
+            Some(0) => builder.pool_idle_timeout(None),
+            Some(ms) => builder.pool_idle_timeout(Duration::from_millis(ms)),
+            None => builder,
+        };
+        let builder = if self.disable_keepalive {
+            builder.pool_max_idle_per_host(0).tcp_keepalive(None)
+        } else {
+            builder
+        };
+        let mut builder = builder
+            .tcp_nodelay(self.tcp_nodelay)
+            .dns_resolver(Arc::new(dns::TimedResolver))
+            .connector_layer(TimingLayer);
+        if self.http1_only {
+            builder = builder.http1_only();
+        }
+        for (host, ips) in &self.dns_overrides {
+            // Port 0: the URL's port (or the scheme's default) is used.
+            let addrs: Vec<_> = ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        if let Some(path) = &self.ca_cert_path {
+            let certs = load_root_certificates(path).map_err(|e| {
+                set_last_error(ERR_CLIENT_INIT, e);
+                ERR_CLIENT_INIT
+            })?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if self.no_proxy_from_env {
+            builder = builder.no_proxy();
+        }
+        let no_proxy = self.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string);
+        let proxies = [
+            self.http_proxy.as_deref().map(reqwest::Proxy::http),
+            self.https_proxy.as_deref().map(reqwest::Proxy::https),
+        ];
+        for proxy in proxies.into_iter().flatten() {
+            let proxy = proxy.map_err(|e| {
+                set_last_error(ERR_CLIENT_INIT, format!("Invalid proxy: {}", e));
+                ERR_CLIENT_INIT
+            })?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+        let identity = self.client_identity.as_ref();
+        let identity = identity.filter(|_| self.cert_verification != CertVerification::SkipChain);
+        if let Some((cert_path, key_path)) = identity {
+            let identity = load_identity(cert_path, key_path).map_err(|e| {
+                set_last_error(ERR_CLIENT_INIT, e);
+                ERR_CLIENT_INIT
+            })?;
+            builder = builder.identity(identity);
+        }
+        Ok(builder)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Request-level settings
+// These are read on every request, so they can be changed at any time.
+// ---------------------------------------------------------------------------
+
+// Largest request body we are willing to send. 0 disables the check.
+static MAX_REQUEST_BYTES: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_max_request_bytes(limit: u64) {
+    MAX_REQUEST_BYTES.store(limit, Ordering::Relaxed);
+}
+
+pub fn max_request_bytes() -> u64 {
+    MAX_REQUEST_BYTES.load(Ordering::Relaxed)
+}
+
+/// Reject a request body over the configured limit before anything is sent.
+pub fn check_request_size(len: u64) -> Result<(), i32> {
+    let limit = MAX_REQUEST_BYTES.load(Ordering::Relaxed);
+    if limit > 0 && len > limit {
+        set_last_error(
+            ERR_REQUEST_TOO_LARGE,
+            format!(
+                "Request body too large: {} bytes exceeds the {} byte limit",
+                len, limit
+            ),
+        );
+        return Err(ERR_REQUEST_TOO_LARGE);
+    }
+    Ok(())
+}
+
+// Largest response body we are willing to read. 0 disables the check.
+static MAX_RESPONSE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_max_response_bytes(limit: u64) {
+    MAX_RESPONSE_BYTES.store(limit, Ordering::Relaxed);
+}
+
+pub fn max_response_bytes() -> u64 {
+    MAX_RESPONSE_BYTES.load(Ordering::Relaxed)
+}
+
+// Most unread responses kept; the oldest are evicted beyond that. 0 disables.
+static MAX_STORED_RESPONSES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_max_stored_responses(limit: usize) {
+    MAX_STORED_RESPONSES.store(limit, Ordering::Relaxed);
+}
+
+pub fn max_stored_responses() -> usize {
+    MAX_STORED_RESPONSES.load(Ordering::Relaxed)
+}
+
+// Requests slower than this are recorded in the slow-request log. 0 disables.
+static SLOW_REQUEST_THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_slow_request_threshold_ms(ms: u64) {
+    SLOW_REQUEST_THRESHOLD_MS.store(ms, Ordering::Relaxed);
+}
+
+pub fn slow_request_threshold_ms() -> u64 {
+    SLOW_REQUEST_THRESHOLD_MS.load(Ordering::Relaxed)
+}
+
+// Relax header-name parsing in request headers JSON. Off by default.
+static ALLOW_NONSTANDARD_HEADER_NAMES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_allow_nonstandard_header_names(enabled: bool) {
+    ALLOW_NONSTANDARD_HEADER_NAMES.store(enabled, Ordering::Relaxed);
+}
+
+pub fn allow_nonstandard_header_names() -> bool {
+    ALLOW_NONSTANDARD_HEADER_NAMES.load(Ordering::Relaxed)
+}
+
+// Send requests as HTTP/1.0 with `Connection: close`. Off by default.
+static FORCE_HTTP10: AtomicBool = AtomicBool::new(false);
+
+pub fn set_force_http10(enabled: bool) {
+    FORCE_HTTP10.store(enabled, Ordering::Relaxed);
+}
+
+pub fn force_http10() -> bool {
+    FORCE_HTTP10.load(Ordering::Relaxed)
+}
+
+// Record a per-thread trace of each request. Off by default.
+static TRACE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_trace(enabled: bool) {
+    TRACE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn trace() -> bool {
+    TRACE.load(Ordering::Relaxed)
+}
+
+// Let identical concurrent requests share one round trip. Off by default.
+static REQUEST_COALESCING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_request_coalescing(enabled: bool) {
+    REQUEST_COALESCING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn request_coalescing() -> bool {
+    REQUEST_COALESCING.load(Ordering::Relaxed)
+}
+
+// Upper bound on the total time of one request including all retries and the
+// pauses between them. 0 (default) means no bound beyond the timeouts.
+static RETRY_BUDGET_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_retry_budget_ms(ms: u64) {
+    RETRY_BUDGET_MS.store(ms, Ordering::Relaxed);
+}
+
+pub fn retry_budget_ms() -> u64 {
+    RETRY_BUDGET_MS.load(Ordering::Relaxed)
+}
+
+// Base that relative request URLs are resolved against. None by default.
+static BASE_URL: OnceLock<Mutex<Option<Url>>> = OnceLock::new();
+
+fn base_url() -> &'static Mutex<Option<Url>> {
+    BASE_URL.get_or_init(|| Mutex::new(None))
+}
+
+pub fn set_base_url(base: Option<Url>) {
+    *base_url().lock().unwrap() = base;
+}
+
+/// Resolve a relative request URL against the base URL. Absolute URLs, and
+/// anything at all when no base is set, pass through unchanged; malformed
+/// URLs are left for the request itself to reject.
+pub fn resolve_url(url: &str) -> Result<Cow<'_, str>, i32> {
+    if Url::parse(url).is_ok() {
+        return Ok(Cow::Borrowed(url));
+    }
+    match base_url().lock().unwrap().as_ref() {
+        Some(base) => base.join(url).map(|u| Cow::Owned(u.into())).map_err(|e| {
+            set_last_error(
+                ERR_INVALID_ARGUMENT,
+                format!("Cannot resolve '{}' against '{}': {}", url, base, e),
+            );
+            ERR_INVALID_ARGUMENT
+        }),
+        None => Ok(Cow::Borrowed(url)),
+    }
+}
+
+// Substrings marking a 2xx body as a disguised error page. Empty (default)
+// disables the check.
+static ERROR_PAGE_MARKERS: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+
+/// How much of the body is searched for error-page markers.
+const ERROR_PAGE_SCAN_BYTES: usize = 64 * 1024;
+
+fn error_page_markers() -> &'static Mutex<Vec<String>> {
+    ERROR_PAGE_MARKERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+pub fn set_error_page_markers(markers: Vec<String>) {
+    *error_page_markers().lock().unwrap() = markers;
+}
+
+/// The first configured marker found in a 2xx response body, if any.
+/// Only the first ERROR_PAGE_SCAN_BYTES are searched, as (lossy) UTF-8.
+pub fn error_page_marker(status: u32, body: &[u8]) -> Option<String> {
+    if !(200..300).contains(&status) {
+        return None;
+    }
+    let markers = error_page_markers().lock().unwrap();
+    if markers.is_empty() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&body[..body.len().min(ERROR_PAGE_SCAN_BYTES)]);
+    markers.iter().find(|m| text.contains(m.as_str())).cloned()
+}
+
+// Extra attempts after a failed TCP/TLS connect. 0 (default) tries once.
+static CONNECT_RETRIES: AtomicU32 = AtomicU32::new(0);
+
+pub fn set_connect_retries(n: u32) {
+    CONNECT_RETRIES.store(n, Ordering::Relaxed);
+}
+
+pub fn connect_retries() -> u32 {
+    CONNECT_RETRIES.load(Ordering::Relaxed)
+}
+
+/// Automatic retries of failed requests, set through http_init.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Extra attempts after a connect failure, or a timeout of an idempotent
+    /// request. 0 (default) disables automatic retries.
+    pub max_retries: u32,
+    /// Wait before the first retry; it doubles for each further one.
+    pub base_backoff_ms: u64,
+    /// Response statuses that are retried too (e.g. 429, 503), waiting as
+    /// long as their Retry-After header asks.
+    pub retry_on_status: Vec<u16>,
+    /// Longest Retry-After wait honoured; longer ones are cut to this.
+    pub max_retry_after_ms: u64,
+}
+
+impl RetryPolicy {
+    const DEFAULT: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        base_backoff_ms: 100,
+        retry_on_status: Vec::new(),
+        max_retry_after_ms: 60_000,
+    };
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static RETRY_POLICY: Mutex<RetryPolicy> = Mutex::new(RetryPolicy::DEFAULT);
+
+pub fn set_retry_policy(policy: RetryPolicy) {
+    *RETRY_POLICY.lock().unwrap() = policy;
+}
+
+pub fn retry_policy() -> RetryPolicy {
+    RETRY_POLICY.lock().unwrap().clone()
+}
+
+fn retry_policy_json() -> serde_json::Value {
+    let policy = retry_policy();
+    serde_json::json!({
+        "max_retries": policy.max_retries,
+        "base_backoff_ms": policy.base_backoff_ms,
+        "retry_on_status": policy.retry_on_status,
+        "max_retry_after_ms": policy.max_retry_after_ms,
+    })
+}
+
+/// The request-level settings as JSON, for http_get_effective_config.
+pub fn request_settings_json() -> serde_json::Value {
+    serde_json::json!({
+        "max_request_bytes": max_request_bytes(),
+        "max_response_bytes": max_response_bytes(),
+        "max_stored_responses": max_stored_responses(),
+        "slow_request_threshold_ms": slow_request_threshold_ms(),
+        "allow_nonstandard_header_names": allow_nonstandard_header_names(),
+        "force_http10": force_http10(),
+        "request_coalescing": request_coalescing(),
+        "trace": trace(),
+        "connect_retries": connect_retries(),
+        "retry_budget_ms": retry_budget_ms(),
+        "retry": retry_policy_json(),
+        "error_page_markers": *error_page_markers().lock().unwrap(),
+        "base_url": base_url().lock().unwrap().as_ref().map(Url::as_str),
+    })
+}
@@ -0,0 +1,168 @@
+// Minimal HTTP-date handling (RFC 7231 section 7.1.1.1), without pulling in a
+// date/time crate. Times are Unix epoch milliseconds, always UTC.
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Inverse of days_from_civil: (year, month, day) for days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Current time as Unix epoch milliseconds.
+pub fn now_unix_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Current time as Unix epoch seconds.
+pub fn now_unix_secs() -> i64 {
+    now_unix_millis().div_euclid(1_000)
+}
+
+fn parse_month(s: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(s))
+        .map(|i| i as u32 + 1)
+}
+
+fn parse_time(s: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = s.split(':');
+    let h = parts.next()?.parse().ok()?;
+    let m = parts.next()?.parse().ok()?;
+    let sec = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || h > 23 || m > 59 || sec > 60 {
+        return None;
+    }
+    Some((h, m, sec))
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+fn to_millis(year: i64, month: u32, day: u32, time: (u32, u32, u32)) -> Option<i64> {
+    if day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * 86_400
+        + time.0 as i64 * 3_600
+        + time.1 as i64 * 60
+        + time.2 as i64;
+    Some(secs * 1_000)
+}
+
+/// Parse an HTTP-date in any of the three formats a recipient must accept:
+///
+/// - IMF-fixdate: `Sun, 06 Nov 1994 08:49:37 GMT`
+/// - RFC 850:     `Sunday, 06-Nov-94 08:49:37 GMT`
+/// - asctime:     `Sun Nov  6 08:49:37 1994`
+///
+/// Returns Unix epoch milliseconds, or None if the string is not a valid date.
+pub fn parse_http_date(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let fields: Vec<&str> = s.split_whitespace().collect();
+
+    if let Some((_, rest)) = s.split_once(',') {
+        let rest: Vec<&str> = rest.split_whitespace().collect();
+        match rest.as_slice() {
+            // IMF-fixdate
+            [day, month, year, time, "GMT"] => {
+                let year: i64 = year.parse().ok()?;
+                to_millis(year, parse_month(month)?, day.parse().ok()?, parse_time(time)?)
+            }
+            // RFC 850: two-digit year, 00-69 is 20xx and 70-99 is 19xx
+            [date, time, "GMT"] => {
+                let mut parts = date.split('-');
+                let day = parts.next()?.parse().ok()?;
+                let month = parse_month(parts.next()?)?;
+                let yy: i64 = parts.next()?.parse().ok()?;
+                let year = if yy < 70 { 2000 + yy } else { 1900 + yy };
+                to_millis(year, month, day, parse_time(time)?)
+            }
+            _ => None,
+        }
+    } else {
+        // asctime
+        match fields.as_slice() {
+            [_, month, day, time, year] => {
+                let year: i64 = year.parse().ok()?;
+                to_millis(year, parse_month(month)?, day.parse().ok()?, parse_time(time)?)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The wait a Retry-After value asks for, in milliseconds: delta-seconds
+/// (`120`) or an HTTP-date, measured from `now_millis`. A date in the past
+/// gives 0; anything else unparseable gives None.
+pub fn retry_after_ms(value: &str, now_millis: i64) -> Option<u64> {
+    let value = value.trim();
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return Some(value.parse::<u64>().unwrap_or(u64::MAX).saturating_mul(1_000));
+    }
+    parse_http_date(value).map(|at| (at - now_millis).max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_three_http_date_formats() {
+        let expected = Some(784_111_777_000);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn parses_retry_after_in_both_forms() {
+        let now = 784_111_777_000;
+        assert_eq!(retry_after_ms("120", now), Some(120_000));
+        assert_eq!(retry_after_ms("Sun, 06 Nov 1994 08:50:07 GMT", now), Some(30_000));
+        assert_eq!(retry_after_ms("Sun, 06 Nov 1994 08:00:00 GMT", now), Some(0));
+        assert_eq!(retry_after_ms("-5", now), None);
+        assert_eq!(retry_after_ms("soon", now), None);
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        for days in [-719_468, -1, 0, 1, 11_016, 19_723, 2_932_896] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+}
@@ -0,0 +1,304 @@
+// HTTP Digest authentication (RFC 7616, and RFC 2069 servers without qop).
+//
+// reqwest has no Digest support, so the request is sent once, the 401
+// challenge is answered and the request repeated. MD5 is implemented here:
+// ring deliberately leaves it out, and Digest is the only place we need it.
+
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Method, Url};
+use ring::digest;
+
+use crate::error::{set_last_error, ERR_REQUEST_FAILED};
+use crate::http::{self, HttpResponse};
+use crate::options::RequestOptions;
+
+/// Per-round constants of MD5 (RFC 1321): floor(abs(sin(i + 1)) * 2^32).
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Left-rotation amounts, four per round.
+const MD5_S: [[u32; 4]; 4] = [
+    [7, 12, 17, 22],
+    [5, 9, 14, 20],
+    [4, 11, 16, 23],
+    [6, 10, 15, 21],
+];
+
+/// MD5 of `data`.
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    for block in message.chunks_exact(64) {
+        let words: Vec<u32> = block
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(MD5_K[i])
+                .wrapping_add(words[g])
+                .rotate_left(MD5_S[i / 16][i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 16];
+    for (chunk, s) in out.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&s.to_le_bytes());
+    }
+    out
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The hash functions a challenge may name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn hash(self, data: &str) -> String {
+        match self {
+            Self::Md5 => hex(&md5(data.as_bytes())),
+            Self::Sha256 => hex(digest::digest(&digest::SHA256, data.as_bytes()).as_ref()),
+        }
+    }
+}
+
+/// A parsed `WWW-Authenticate: Digest ...` challenge.
+#[derive(Debug, Default)]
+pub struct Challenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    /// As sent, e.g. "MD5-sess"; None means MD5.
+    pub algorithm: Option<String>,
+    /// The qop options offered, e.g. ["auth", "auth-int"]; empty for an
+    /// RFC 2069 server.
+    pub qop: Vec<String>,
+}
+
+/// Split auth-params (`key=token` or `key="quoted \"string\""`, comma
+/// separated) into pairs. Keys are lowercased.
+fn auth_params(mut rest: &str) -> Option<Vec<(String, String)>> {
+    let mut params = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return Some(params);
+        }
+        let eq = rest.find('=')?;
+        let key = rest[..eq].trim().to_ascii_lowercase();
+        rest = rest[eq + 1..].trim_start();
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => value.push(chars.next()?.1),
+                    (_, ch) => value.push(ch),
+                }
+            };
+            rest = &quoted[end + 1..];
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = rest[..end].trim().to_string();
+            rest = &rest[end..];
+            value
+        };
+        params.push((key, value));
+    }
+}
+
+/// Find the Digest challenge among a response's WWW-Authenticate headers.
+/// A header holding several challenges is not split: servers that offer
+/// Digest alongside Basic send them as separate headers.
+pub fn parse_challenge(headers: &HeaderMap) -> Option<Challenge> {
+    headers.get_all(WWW_AUTHENTICATE).iter().find_map(|value| {
+        let value = value.to_str().ok()?.trim_start();
+        let (scheme, rest) = value.split_once(|c: char| c.is_ascii_whitespace())?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let mut challenge = Challenge::default();
+        let mut has_nonce = false;
+        for (key, value) in auth_params(rest)? {
+            match key.as_str() {
+                "realm" => challenge.realm = value,
+                "nonce" => {
+                    challenge.nonce = value;
+                    has_nonce = true;
+                }
+                "opaque" => challenge.opaque = Some(value),
+                "algorithm" => challenge.algorithm = Some(value),
+                "qop" => challenge.qop = value.split(',').map(|q| q.trim().to_string()).collect(),
+                _ => {}
+            }
+        }
+        has_nonce.then_some(challenge)
+    })
+}
+
+/// Quote a value for the Authorization header.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The Authorization header value answering `challenge` for a request of
+/// `method` to `uri` (path and query), with client nonce `cnonce`. Fails
+/// with a message for algorithms or qop options other than MD5, MD5-sess,
+/// SHA-256, SHA-256-sess and auth.
+pub fn authorization(
+    challenge: &Challenge,
+    method: &str,
+    uri: &str,
+    username: &str,
+    password: &str,
+    cnonce: &str,
+) -> Result<String, String> {
+    let name = challenge.algorithm.as_deref().unwrap_or("MD5");
+    let (algorithm, sess) = match name.to_ascii_uppercase().as_str() {
+        "MD5" => (Algorithm::Md5, false),
+        "MD5-SESS" => (Algorithm::Md5, true),
+        "SHA-256" => (Algorithm::Sha256, false),
+        "SHA-256-SESS" => (Algorithm::Sha256, true),
+        _ => return Err(format!("Unsupported Digest algorithm '{}'", name)),
+    };
+    let qop = match challenge.qop.as_slice() {
+        [] => None,
+        offered if offered.iter().any(|q| q.eq_ignore_ascii_case("auth")) => Some("auth"),
+        offered => {
+            return Err(format!(
+                "Unsupported Digest qop '{}'; only auth is",
+                offered.join(",")
+            ))
+        }
+    };
+    let nc = "00000001";
+
+    let mut ha1 = algorithm.hash(&format!("{}:{}:{}", username, challenge.realm, password));
+    if sess {
+        ha1 = algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+    }
+    let ha2 = algorithm.hash(&format!("{}:{}", method, uri));
+    let response = match qop {
+        Some(qop) => algorithm.hash(&format!(
+            "{}:{}:{}:{}:{}:{}",
+            ha1, challenge.nonce, nc, cnonce, qop, ha2
+        )),
+        None => algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, ha2)),
+    };
+
+    let mut header = format!(
+        "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response={}",
+        quote(username),
+        quote(&challenge.realm),
+        quote(&challenge.nonce),
+        quote(uri),
+        name,
+        quote(&response)
+    );
+    if let Some(qop) = qop {
+        header.push_str(&format!(
+            ", qop={}, nc={}, cnonce={}",
+            qop,
+            nc,
+            quote(cnonce)
+        ));
+    }
+    if let Some(opaque) = &challenge.opaque {
+        header.push_str(&format!(", opaque={}", quote(opaque)));
+    }
+    Ok(header)
+}
+
+/// The digest-uri for a request: the path with any query.
+fn request_uri(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// GET `url`, answering a Digest challenge with the credentials. A response
+/// other than a 401 with a Digest challenge is returned as it is, as is the
+/// second response whatever its status.
+pub fn get(
+    url: &str,
+    headers: HeaderMap,
+    username: &str,
+    password: &str,
+    options: &RequestOptions,
+) -> Result<HttpResponse, i32> {
+    let first = http::request(Method::GET, url, headers.clone(), Vec::new(), options)?;
+    if first.status != 401 {
+        return Ok(first);
+    }
+    let Some(challenge) = parse_challenge(&first.headers) else {
+        return Ok(first);
+    };
+    // Answer for the URL that issued the challenge, after any redirects.
+    let cnonce = format!("{:016x}", fastrand::u64(..));
+    let value = authorization(
+        &challenge,
+        "GET",
+        &request_uri(&first.url),
+        username,
+        password,
+        &cnonce,
+    )
+    .map_err(|e| {
+        set_last_error(ERR_REQUEST_FAILED, e);
+        ERR_REQUEST_FAILED
+    })?;
+    let mut headers = headers;
+    // Credentials prepared by the caller would only conflict.
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&value).map_err(|_| {
+            set_last_error(
+                ERR_REQUEST_FAILED,
+                "Digest credentials are not valid header text",
+            );
+            ERR_REQUEST_FAILED
+        })?,
+    );
+    http::request(
+        Method::GET,
+        first.url.as_str(),
+        headers,
+        Vec::new(),
+        options,
+    )
+}
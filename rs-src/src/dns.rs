@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::error::{set_last_error, ERR_DNS};
+use crate::{runtime, timing, workers};
+
+/// Resolve `host` to its IP addresses the way the client does when it
+/// connects: a host pinned by dns_overrides gets its configured addresses,
+/// any other goes to the system resolver. Duplicates are removed, keeping
+/// the order the resolver returned (the order connections are attempted in).
+pub fn resolve(host: &str) -> Result<Vec<IpAddr>, i32> {
+    // IPv6 literals may arrive bracketed, as they appear in URLs.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Some(ips) = runtime::dns_override(host) {
+        return Ok(ips);
+    }
+    let addrs = (host, 0).to_socket_addrs().map_err(|e| {
+        set_last_error(ERR_DNS, format!("DNS lookup for '{}' failed: {}", host, e));
+        ERR_DNS
+    })?;
+
+    let mut ips = Vec::new();
+    for addr in addrs {
+        if !ips.contains(&addr.ip()) {
+            ips.push(addr.ip());
+        }
+    }
+    if ips.is_empty() {
+        set_last_error(ERR_DNS, format!("DNS lookup for '{}' returned no addresses", host));
+        return Err(ERR_DNS);
+    }
+    Ok(ips)
+}
+
+type LookupResult = Result<Addrs, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The client's resolver: the system resolver, as reqwest uses by default,
+/// run on a tracked worker thread and timed so requests can report how long
+/// the lookup for their connection took.
+pub struct TimedResolver;
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let slot = timing::lookup_slot();
+        let pending = Arc::new(Mutex::new(PendingLookup::default()));
+        let shared = pending.clone();
+        let spawned = workers::spawn("dns", move || {
+            let started = Instant::now();
+            let result = (host.as_str(), 0).to_socket_addrs();
+            if let Some(slot) = slot {
+                *slot.lock().unwrap() = Some(started.elapsed());
+            }
+            let result: LookupResult = match result {
+                Ok(addrs) => Ok(Box::new(addrs.collect::<Vec<_>>().into_iter())),
+                Err(e) => Err(Box::new(e)),
+            };
+            let mut pending = shared.lock().unwrap();
+            pending.result = Some(result);
+            if let Some(waker) = pending.waker.take() {
+                waker.wake();
+            }
+        });
+        if let Err(e) = spawned {
+            return Box::pin(async move { Err(e.into()) });
+        }
+        Box::pin(LookupFuture(pending))
+    }
+}
+
+#[derive(Default)]
+struct PendingLookup {
+    result: Option<LookupResult>,
+    waker: Option<Waker>,
+}
+
+/// Completes when the lookup thread has stored its result.
+struct LookupFuture(Arc<Mutex<PendingLookup>>);
+
+impl Future for LookupFuture {
+    type Output = LookupResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<LookupResult> {
+        let mut pending = self.0.lock().unwrap();
+        match pending.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                pending.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Streaming downloads to disk.
+//
+// Writes use ordinary file I/O rather than a mapping of our own. The OS page
+// cache is shared with any mapping the caller opens afterwards, so the data is
+// still only copied once, and this stays portable without an mmap dependency.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use reqwest::header::HeaderMap;
+
+use crate::error::{set_last_error, ERR_IO};
+use crate::http;
+
+/// Size of each read from the network and write to disk.
+const COPY_CHUNK: usize = 64 * 1024;
+
+/// Outcome of a download to file.
+pub struct Download {
+    pub status: u32,
+    /// Bytes written, which is also the final file size.
+    pub size: u64,
+}
+
+/// GET `url` and write the body straight to `path`, never holding more than a
+/// small buffer in memory.
+///
+/// When the length is known up front (Content-Length, else `size_hint` > 0)
+/// the file is first extended to that size so the filesystem can allocate it
+/// in one go and the caller can map it immediately. If the body turns out to
+/// be a different length the file is trimmed or extended to match what was
+/// actually received.
+///
+/// `progress` is called on this thread with (bytes written, Content-Length or
+/// 0 if unknown): once when the response arrives and after each chunk.
+pub fn to_file(
+    url: &str,
+    headers: HeaderMap,
+    path: &Path,
+    size_hint: u64,
+    timeout_ms: i32,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<Download, i32> {
+    // Fail before the request is sent rather than after the body starts.
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        if !dir.is_dir() {
+            set_last_error(ERR_IO, format!("Directory '{}' does not exist", dir.display()));
+            return Err(ERR_IO);
+        }
+    }
+
+    let (status, mut response) = http::stream(url, headers, timeout_ms)?;
+    let total = response.content_length();
+    let expected = total.or((size_hint > 0).then_some(size_hint));
+
+    let io_error = |what: &str, e: io::Error| {
+        set_last_error(ERR_IO, format!("Failed to {} '{}': {}", what, path.display(), e));
+        ERR_IO
+    };
+
+    let mut file = File::create(path).map_err(|e| io_error("create", e))?;
+    if let Some(len) = expected {
+        file.set_len(len).map_err(|e| io_error("preallocate", e))?;
+    }
+
+    let mut chunk = vec![0u8; COPY_CHUNK];
+    let mut size = 0u64;
+    progress(0, total.unwrap_or(0));
+    loop {
+        let n = match response.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                let code = http::classify_io(&e);
+                set_last_error(code, format!("Failed to read response body: {}", e));
+                return Err(code);
+            }
+        };
+        file.write_all(&chunk[..n]).map_err(|e| io_error("write", e))?;
+        size += n as u64;
+        progress(size, total.unwrap_or(0));
+    }
+
+    if expected != Some(size) {
+        file.set_len(size).map_err(|e| io_error("resize", e))?;
+    }
+    file.sync_all().map_err(|e| io_error("flush", e))?;
+
+    Ok(Download { status, size })
+}
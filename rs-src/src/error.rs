@@ -9,27 +9,77 @@ pub const ERR_REQUEST_FAILED: i32 = -4;
 pub const ERR_INVALID_HANDLE: i32 = -5;
 pub const ERR_BUFFER_TOO_SMALL: i32 = -6;
 pub const ERR_CLIENT_INIT: i32 = -7;
+pub const ERR_LONG_POLL_TIMEOUT: i32 = -8;
+pub const ERR_INVALID_ARGUMENT: i32 = -9;
+pub const ERR_REQUEST_TOO_LARGE: i32 = -10;
+pub const ERR_INVALID_METHOD: i32 = -11;
+pub const ERR_TIMEOUT: i32 = -12;
+pub const ERR_STREAM_CLOSED: i32 = -13;
+pub const ERR_IO: i32 = -14;
+pub const ERR_DNS: i32 = -15;
+pub const ERR_SOFT_ERROR_PAGE: i32 = -16;
+pub const ERR_CONNECT: i32 = -17;
+pub const ERR_BODY_TOO_LARGE: i32 = -18;
+pub const ERR_CANCELLED: i32 = -19;
 
 use std::cell::RefCell;
 
-// Thread-local storage for the last error message.
-// Using thread-local means concurrent calls from different LabVIEW threads
-// never clobber each other's error strings.
+/// A fixed description of an error code, for logging a stored code without
+/// the thread-local message that came with it.
+pub fn describe(code: i32) -> &'static str {
+    match code {
+        ERR_OK => "Success",
+        ERR_NULL_PTR => "Null pointer argument",
+        ERR_INVALID_UTF8 => "Invalid UTF-8 in input",
+        ERR_INVALID_HEADERS => "Invalid headers JSON",
+        ERR_REQUEST_FAILED => "Request failed",
+        ERR_INVALID_HANDLE => "Invalid or already-consumed handle",
+        ERR_BUFFER_TOO_SMALL => "Buffer too small",
+        ERR_CLIENT_INIT => "HTTP client initialisation failed",
+        ERR_LONG_POLL_TIMEOUT => "Long poll ended without data",
+        ERR_INVALID_ARGUMENT => "Invalid argument",
+        ERR_REQUEST_TOO_LARGE => "Request body too large",
+        ERR_INVALID_METHOD => "Invalid HTTP method",
+        ERR_TIMEOUT => "Request timed out",
+        ERR_STREAM_CLOSED => "Stream has ended",
+        ERR_IO => "File or I/O error",
+        ERR_DNS => "DNS lookup failed",
+        ERR_SOFT_ERROR_PAGE => "Response is an error page",
+        ERR_CONNECT => "Could not connect",
+        ERR_BODY_TOO_LARGE => "Response body too large",
+        ERR_CANCELLED => "Request was cancelled",
+        _ => "Unknown error",
+    }
+}
+
+// Thread-local storage for the last error: its code and message, kept together
+// so they always describe the same failure. Using thread-local means
+// concurrent calls from different LabVIEW threads never clobber each other's
+// errors.
 thread_local! {
-    static LAST_ERROR: RefCell<String> = RefCell::new(String::new());
+    static LAST_ERROR: RefCell<(i32, String)> = const { RefCell::new((ERR_OK, String::new())) };
 }
 
-/// Store an error message for retrieval via http_get_last_error.
-pub fn set_last_error(msg: impl Into<String>) {
+/// Store an error code and message for retrieval via http_get_last_error and
+/// http_get_last_error_ex. `code` is the value the failing call returns; a
+/// warning attached to a successful call is stored with ERR_OK.
+pub fn set_last_error(code: i32, msg: impl Into<String>) {
     LAST_ERROR.with(|e| {
-        *e.borrow_mut() = msg.into();
+        *e.borrow_mut() = (code, msg.into());
     });
 }
 
+/// The last error message set on this thread.
+pub fn last_error() -> String {
+    LAST_ERROR.with(|e| e.borrow().1.clone())
+}
+
 /// Clear the last error.
 pub fn clear_last_error() {
     LAST_ERROR.with(|e| {
-        e.borrow_mut().clear();
+        let mut e = e.borrow_mut();
+        e.0 = ERR_OK;
+        e.1.clear();
     });
 }
 
@@ -39,17 +89,32 @@ pub fn read_last_error(buf_ptr: *mut u8, buf_len: i32) -> i32 {
     if buf_ptr.is_null() || buf_len <= 0 {
         return ERR_NULL_PTR;
     }
+    LAST_ERROR.with(|e| copy_truncated(&e.borrow().1, buf_ptr, buf_len))
+}
 
+/// read_last_error that also writes the error's code to `code_out`, taken from
+/// the same record as the message so the two always match.
+pub fn read_last_error_ex(code_out: *mut i32, buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    if code_out.is_null() || buf_ptr.is_null() || buf_len <= 0 {
+        return ERR_NULL_PTR;
+    }
     LAST_ERROR.with(|e| {
         let error = e.borrow();
-        let bytes = error.as_bytes();
-        // Leave room for a null terminator
-        let copy_len = bytes.len().min((buf_len as usize).saturating_sub(1));
         unsafe {
-            std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf_ptr, copy_len);
-            // Write null terminator
-            *buf_ptr.add(copy_len) = 0;
+            *code_out = error.0;
         }
-        copy_len as i32
+        copy_truncated(&error.1, buf_ptr, buf_len)
     })
 }
+
+/// Copy as much of `msg` as fits, always leaving room for a null terminator.
+fn copy_truncated(msg: &str, buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    let bytes = msg.as_bytes();
+    let copy_len = bytes.len().min((buf_len as usize).saturating_sub(1));
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf_ptr, copy_len);
+        // Write null terminator
+        *buf_ptr.add(copy_len) = 0;
+    }
+    copy_len as i32
+}
@@ -0,0 +1,284 @@
+// Minimal gzip (RFC 1952) encoder.
+//
+// The body is compressed as DEFLATE (RFC 1951) with LZ77 matching and the
+// fixed Huffman code, falling back to stored blocks when that would be larger.
+// Fixed codes compress a little worse than dynamic ones but keep the encoder
+// small, and the output is valid gzip that any decoder accepts.
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash to try before giving up.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+/// Largest payload of a single stored block.
+const MAX_STORED: usize = 65_535;
+
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Compress `data` into a complete gzip member.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // Header: magic, CM=deflate, no flags, no mtime, XFL=0, OS=unknown.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+
+    let fixed = deflate_fixed(data);
+    let stored_len = data.len() + 5 * data.len().div_ceil(MAX_STORED).max(1);
+    if fixed.len() <= stored_len {
+        out.extend_from_slice(&fixed);
+    } else {
+        deflate_stored(data, &mut out);
+    }
+
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// CRC-32 (IEEE 802.3, reflected), as used by gzip and zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0u32; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            }
+            *entry = c;
+        }
+        table
+    });
+    let mut crc = !0u32;
+    for &b in data {
+        crc = table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Emit `data` as uncompressed stored blocks.
+fn deflate_stored(data: &[u8], out: &mut Vec<u8>) {
+    let mut chunks = data.chunks(MAX_STORED).peekable();
+    if chunks.peek().is_none() {
+        // An empty input still needs one (final, empty) block.
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        return;
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Emit `data` as a single final block using the fixed Huffman code.
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.write(1, 1); // BFINAL
+    bits.write(1, 2); // BTYPE = fixed Huffman
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let (len, dist) = longest_match(data, pos, &head, &prev);
+        if len >= MIN_MATCH {
+            write_length(&mut bits, len);
+            write_distance(&mut bits, dist);
+            for p in pos..pos + len {
+                insert(data, p, &mut head, &mut prev);
+            }
+            pos += len;
+        } else {
+            write_literal(&mut bits, data[pos] as u16);
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    write_literal(&mut bits, 256); // end of block
+    bits.finish()
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let v = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH > data.len() {
+        return;
+    }
+    let h = hash(data, pos);
+    prev[pos % WINDOW] = head[h];
+    head[h] = pos;
+}
+
+/// Longest earlier match for the bytes at `pos`, as (length, distance).
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+    let max_len = MAX_MATCH.min(data.len() - pos);
+    let (mut best_len, mut best_dist) = (0, 0);
+    let mut candidate = head[hash(data, pos)];
+
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW {
+            break;
+        }
+        let len = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_len])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if len > best_len {
+            best_len = len;
+            best_dist = pos - candidate;
+            if len == max_len {
+                break;
+            }
+        }
+        let next = prev[candidate % WINDOW];
+        // Stop once the chain points forwards, i.e. into overwritten slots.
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+    (best_len, best_dist)
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u16) {
+    let (code, len) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    };
+    bits.write_code(code as u32, len);
+}
+
+fn write_length(bits: &mut BitWriter, len: usize) {
+    let i = LEN_BASE.iter().rposition(|&base| base as usize <= len).unwrap();
+    write_literal(bits, 257 + i as u16);
+    bits.write((len - LEN_BASE[i] as usize) as u32, LEN_EXTRA[i] as u32);
+}
+
+fn write_distance(bits: &mut BitWriter, dist: usize) {
+    let i = DIST_BASE.iter().rposition(|&base| base as usize <= dist).unwrap();
+    bits.write_code(i as u32, 5);
+    bits.write((dist - DIST_BASE[i] as usize) as u32, DIST_EXTRA[i] as u32);
+}
+
+/// Packs bits least-significant first, as DEFLATE requires.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n: u32) {
+        self.acc |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most-significant bit first.
+    fn write_code(&mut self, code: u32, n: u32) {
+        self.write(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn repetitive_input_uses_fixed_huffman_block() {
+        let data = vec![b'a'; 10_000];
+        let gz = compress(&data);
+        assert!(gz.len() < 200);
+        // BFINAL = 1, BTYPE = 01 in the first three bits after the header.
+        assert_eq!(gz[10] & 0b111, 0b011);
+    }
+
+    #[test]
+    fn incompressible_input_falls_back_to_stored_blocks() {
+        let mut state = 0x2545_F491u32;
+        let data: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let gz = compress(&data);
+        // Two stored blocks: 5 bytes of framing each, plus header and trailer.
+        assert_eq!(gz.len(), 10 + data.len() + 2 * 5 + 8);
+        assert_eq!(gz[10], 0);
+    }
+
+    // The members below were checked to decode to their input with a real
+    // decoder, Python's gzip.decompress. They pin the encoder's exact output,
+    // so a change to the matcher or block choice needs them regenerated and
+    // checked again the same way.
+
+    #[test]
+    fn fixed_huffman_member_decodes_with_a_real_decoder() {
+        let mut data = b"Hello, hello, hello! LabVIEW says hello. ".to_vec();
+        data.extend_from_slice(&[b'a'; 300]);
+        data.extend_from_slice(b"Hello, hello!");
+        let checked: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\xff\xf3\x48\xcd\xc9\xc9\
+            \xd7\x51\xc8\x40\xa2\x14\x15\x7c\x12\x93\xc2\x3c\x5d\xc3\x15\x8a\x13\x2b\x8b\
+            \x21\x62\x7a\x0a\x89\xa3\x80\x68\x80\x1c\xa8\x8a\x00\x6e\x04\x38\x20\x62\x01\
+            \x00\x00";
+        assert_eq!(compress(&data), checked);
+    }
+
+    #[test]
+    fn stored_member_decodes_with_a_real_decoder() {
+        let data: Vec<u8> = (0xE0..=0xFF).collect();
+        let mut checked = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\xff\x01\x20\x00\xdf\xff".to_vec();
+        checked.extend_from_slice(&data);
+        checked.extend_from_slice(b"\xd4\x11\xd6\x7c\x20\x00\x00\x00");
+        assert_eq!(compress(&data), checked);
+    }
+}
@@ -1,11 +1,32 @@
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::borrow::Cow;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 
-use crate::error::{set_last_error, ERR_INVALID_HEADERS, ERR_INVALID_UTF8};
+use crate::config::allow_nonstandard_header_names;
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT, ERR_INVALID_HEADERS, ERR_INVALID_UTF8};
+
+/// Parse a header name. Any RFC 7230 token is accepted, which already includes
+/// names like `X_Custom_Thing`. In permissive mode, surrounding whitespace and
+/// a trailing colon (common when names are templated from "Name: value" text)
+/// are stripped first.
+fn parse_header_name(key: &str) -> Result<HeaderName, reqwest::header::InvalidHeaderName> {
+    if allow_nonstandard_header_names() {
+        let trimmed = key.trim();
+        let trimmed = trimmed.strip_suffix(':').unwrap_or(trimmed).trim_end();
+        HeaderName::from_bytes(trimmed.as_bytes())
+    } else {
+        HeaderName::from_bytes(key.as_bytes())
+    }
+}
 
 /// Parse a null-terminated JSON string of the form {"Key": "Value", ...}
-/// into a reqwest HeaderMap.
+/// into a reqwest HeaderMap. A value may also be an array, which sends the
+/// header once per element, e.g. {"X-Forwarded-For": ["a", "b"]}.
+///
+/// Numbers and booleans are sent as their JSON text, so {"X-Retry-Count": 3}
+/// sends `X-Retry-Count: 3` and {"X-Dry-Run": true} sends `X-Dry-Run: true`.
+/// Objects and null are rejected with ERR_INVALID_HEADERS.
 ///
 /// Returns Ok(HeaderMap) on success, or a negative error code on failure.
 /// Passing a null pointer returns an empty HeaderMap (no headers).
@@ -18,7 +39,7 @@ pub fn parse_headers(headers_json: *const c_char) -> Result<HeaderMap, i32> {
     let json_str = unsafe { CStr::from_ptr(headers_json) }
         .to_str()
         .map_err(|_| {
-            set_last_error("Headers JSON string contains invalid UTF-8");
+            set_last_error(ERR_INVALID_UTF8, "Headers JSON string contains invalid UTF-8");
             ERR_INVALID_UTF8
         })?;
 
@@ -29,30 +50,118 @@ pub fn parse_headers(headers_json: *const c_char) -> Result<HeaderMap, i32> {
 
     let map: serde_json::Map<String, serde_json::Value> =
         serde_json::from_str(json_str).map_err(|e| {
-            set_last_error(format!("Failed to parse headers JSON: {}", e));
+            set_last_error(ERR_INVALID_HEADERS, format!("Failed to parse headers JSON: {}", e));
             ERR_INVALID_HEADERS
         })?;
 
+    header_map_from_json(map)
+}
+
+/// Convert an already-parsed JSON object of {"Key": "Value"} pairs into a
+/// HeaderMap, with the same validation (and array values) as parse_headers.
+pub fn header_map_from_json(
+    map: serde_json::Map<String, serde_json::Value>,
+) -> Result<HeaderMap, i32> {
     let mut header_map = HeaderMap::new();
 
     for (key, value) in map {
-        let header_name = HeaderName::from_bytes(key.as_bytes()).map_err(|e| {
-            set_last_error(format!("Invalid header name '{}': {}", key, e));
+        let header_name = parse_header_name(&key).map_err(|e| {
+            set_last_error(ERR_INVALID_HEADERS, format!("Invalid header name '{}': {}", key, e));
             ERR_INVALID_HEADERS
         })?;
 
-        let value_str = value.as_str().ok_or_else(|| {
-            set_last_error(format!("Header value for '{}' must be a string", key));
-            ERR_INVALID_HEADERS
-        })?;
+        match value {
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    let Some(text) = scalar_text(&value) else {
+                        set_last_error(
+                            ERR_INVALID_HEADERS,
+                            format!(
+                            "Header values for '{}' must be strings, numbers or booleans, got {}",
+                            key, value
+                        ),
+                        );
+                        return Err(ERR_INVALID_HEADERS);
+                    };
+                    header_map.append(&header_name, header_value(&key, &text)?);
+                }
+            }
+            value => {
+                let text = scalar_text(&value).ok_or_else(|| {
+                    set_last_error(
+                        ERR_INVALID_HEADERS,
+                        format!(
+                        "Header value for '{}' must be a string, number, boolean or array, got {}",
+                        key, value
+                    ),
+                    );
+                    ERR_INVALID_HEADERS
+                })?;
+                header_map.insert(header_name, header_value(&key, &text)?);
+            }
+        }
+    }
 
-        let header_value = HeaderValue::from_str(value_str).map_err(|e| {
-            set_last_error(format!("Invalid header value for '{}': {}", key, e));
-            ERR_INVALID_HEADERS
-        })?;
+    Ok(header_map)
+}
 
-        header_map.insert(header_name, header_value);
+/// A string as-is, or a number or boolean as its JSON text (3, 2.5, true).
+/// Objects, arrays and null give None.
+fn scalar_text(value: &serde_json::Value) -> Option<Cow<'_, str>> {
+    match value {
+        serde_json::Value::String(s) => Some(Cow::Borrowed(s)),
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+            Some(Cow::Owned(value.to_string()))
+        }
+        _ => None,
     }
+}
 
-    Ok(header_map)
+fn header_value(key: &str, value: &str) -> Result<HeaderValue, i32> {
+    HeaderValue::from_str(value).map_err(|e| {
+        set_last_error(ERR_INVALID_HEADERS, format!("Invalid header value for '{}': {}", key, e));
+        ERR_INVALID_HEADERS
+    })
+}
+
+/// All values of `name` joined with ", " (as HTTP allows for repeated
+/// headers), or None if the header is absent. Non-UTF-8 bytes are replaced.
+pub fn joined_value(headers: &HeaderMap, name: &HeaderName) -> Option<String> {
+    let values: Vec<_> = headers
+        .get_all(name)
+        .iter()
+        .map(|v| String::from_utf8_lossy(v.as_bytes()))
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+/// Parse a header name given as a lookup key (any case).
+pub fn lookup_name(name: &str) -> Result<HeaderName, i32> {
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid header name: '{}'", name));
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+/// Build a JSON object from (name, value) pairs. A name that appears more
+/// than once becomes an array of its values, in order.
+pub fn pairs_to_json<'a>(
+    pairs: impl IntoIterator<Item = (&'a str, String)>,
+) -> serde_json::Map<String, serde_json::Value> {
+    use serde_json::Value;
+
+    let mut map = serde_json::Map::new();
+    for (name, value) in pairs {
+        match map.get_mut(name) {
+            Some(Value::Array(values)) => values.push(Value::String(value)),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, Value::String(value)]);
+            }
+            None => {
+                map.insert(name.to_string(), Value::String(value));
+            }
+        }
+    }
+    map
 }
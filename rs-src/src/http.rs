@@ -1,82 +1,758 @@
-use std::time::Duration;
-use reqwest::header::HeaderMap;
+use std::error::Error as _;
+use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use reqwest::blocking::{Body, Request, RequestBuilder, Response};
+use reqwest::header::{
+    HeaderMap, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_TYPE, RETRY_AFTER,
+};
+use reqwest::{Method, Version};
 
-use crate::error::{set_last_error, ERR_REQUEST_FAILED};
+use crate::backoff;
+use crate::cancel;
+use crate::charset::Charset;
+use crate::coalesce;
+use crate::config;
+use crate::date;
+use crate::error::{
+    last_error, set_last_error, ERR_BODY_TOO_LARGE, ERR_CANCELLED, ERR_CONNECT, ERR_DNS,
+    ERR_INVALID_ARGUMENT, ERR_REQUEST_FAILED, ERR_TIMEOUT,
+};
+use crate::jsonpath;
+use crate::mirror;
+use crate::options::RequestOptions;
 use crate::runtime::get_client;
+use crate::slowlog;
+use crate::timing;
+use crate::trace;
 
+/// Extra time allowed on top of a long-poll hold period before the client
+/// itself times out, so a server that answers right at its deadline still wins.
+const LONG_POLL_SLACK_MS: i32 = 5_000;
+
+/// Pause between connection attempts when connect retries are enabled.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Most requests http_get_batch runs at once.
+const MAX_BATCH_THREADS: usize = 16;
+
+/// Longest wait between automatic retries.
+const MAX_RETRY_BACKOFF_MS: u64 = 30_000;
+
+#[derive(Clone)]
 pub struct HttpResponse {
     pub status: u32,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
+    /// ALPN protocol negotiated over TLS ("h2" or "http/1.1"); empty for plain HTTP.
+    pub alpn: &'static str,
+    /// HTTP version the response arrived over.
+    pub version: Version,
+    /// Reason phrase from the status line, or the canonical one for the
+    /// status when the server sent the standard text (or none, as in HTTP/2).
+    pub reason: String,
+    /// Final URL, after any redirects.
+    pub url: reqwest::Url,
+    /// Time from the first send attempt until the body was fully read,
+    /// including any retries.
+    pub elapsed: Duration,
+    /// DNS lookup made to open a connection for the final attempt; None when
+    /// a pooled connection was reused or the host was an IP address.
+    pub dns: Option<Duration>,
+    /// TCP connect plus any TLS handshake of that connection; None when a
+    /// pooled connection was reused.
+    pub connect: Option<Duration>,
+    /// Time from the start of the final attempt until the response headers
+    /// arrived, including connection setup and any redirects.
+    pub ttfb: Duration,
+}
+
+impl HttpResponse {
+    /// True when a long-poll round ended without data (204, or 2xx with no body).
+    pub fn is_long_poll_timeout(&self) -> bool {
+        self.status == 204 || ((200..300).contains(&self.status) && self.body.is_empty())
+    }
+}
+
+/// A request whose response headers have arrived but whose body has not been read.
+struct InFlight {
+    method: Method,
+    url: reqwest::Url,
+    started: Instant,
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    ttfb: Duration,
+    response: Response,
+}
+
+impl InFlight {
+    /// Read the whole body and build the stored response. A body over
+    /// `max_body` bytes (0 for no limit) fails with ERR_BODY_TOO_LARGE: at
+    /// once if Content-Length declares it, otherwise as soon as that much has
+    /// been read.
+    fn finish(self, max_body: u64) -> Result<HttpResponse, i32> {
+        let InFlight {
+            method,
+            url,
+            started,
+            dns,
+            connect,
+            ttfb,
+            response,
+        } = self;
+
+        let status = response.status().as_u16() as u32;
+        let headers = response.headers().clone();
+        let version = response.version();
+        let reason = reason_phrase(&response);
+        let alpn = negotiated_alpn(response.url(), version);
+        let final_url = response.url().clone();
+
+        let body = if max_body == 0 {
+            response.bytes().map_err(|e| {
+                trace::note(&format!("body read failed: {}", e));
+                let code = classify(&e);
+                set_last_error(code, format!("Failed to read response body: {}", e));
+                code
+            })?
+        } else {
+            read_limited(response, max_body)?.into()
+        };
+
+        let elapsed = started.elapsed();
+        trace::note(&format!("body complete: {} bytes", body.len()));
+        slowlog::record(method.as_str(), url.as_str(), elapsed.as_millis() as u64);
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body: body.to_vec(),
+            alpn,
+            version,
+            reason,
+            url: final_url,
+            elapsed,
+            dns,
+            connect,
+            ttfb,
+        })
+    }
 }
 
-/// Internal helper: attach headers and timeout to a RequestBuilder, then execute.
-fn execute(
-    builder: reqwest::blocking::RequestBuilder,
+/// Read a body of at most `limit` bytes, refusing a larger one without
+/// downloading it when Content-Length gives its size. The connection is
+/// dropped rather than drained in that case.
+fn read_limited(response: Response, limit: u64) -> Result<Vec<u8>, i32> {
+    let too_large = |size: &str| {
+        set_last_error(
+            ERR_BODY_TOO_LARGE,
+            format!("Response body {} exceeds the {} byte limit", size, limit),
+        );
+        ERR_BODY_TOO_LARGE
+    };
+    if let Some(len) = response.content_length().filter(|&len| len > limit) {
+        return Err(too_large(&format!("of {} bytes", len)));
+    }
+    let mut body = Vec::new();
+    response.take(limit + 1).read_to_end(&mut body).map_err(|e| {
+        trace::note(&format!("body read failed: {}", e));
+        let code = classify_io(&e);
+        set_last_error(code, format!("Failed to read response body: {}", e));
+        code
+    })?;
+    if body.len() as u64 > limit {
+        return Err(too_large("read so far"));
+    }
+    Ok(body)
+}
+
+/// The most specific error code for a failed request: ERR_TIMEOUT,
+/// ERR_DNS, ERR_CONNECT, or ERR_REQUEST_FAILED for anything else (TLS
+/// handshake failures count as connect errors; failure_message tells them
+/// apart).
+pub fn classify(e: &reqwest::Error) -> i32 {
+    let mut source = e.source();
+    while let Some(s) = source {
+        if s.is::<cancel::Cancelled>() {
+            return ERR_CANCELLED;
+        }
+        source = s.source();
+    }
+    if e.is_timeout() {
+        return ERR_TIMEOUT;
+    }
+    if !e.is_connect() {
+        return ERR_REQUEST_FAILED;
+    }
+    // hyper-util reports resolver failures as a connect error whose message
+    // is "dns error"; the error type itself is private.
+    let mut source = e.source();
+    while let Some(s) = source {
+        if s.to_string() == "dns error" {
+            return ERR_DNS;
+        }
+        source = s.source();
+    }
+    ERR_CONNECT
+}
+
+/// The rustls error behind a failed TLS handshake, if that is what `e` is.
+/// rustls reports through (nested) io::Errors, whose source() skips the
+/// wrapped error, so each one in the chain is opened with get_ref().
+fn tls_failure(e: &reqwest::Error) -> Option<&rustls::Error> {
+    let mut source = e.source();
+    while let Some(s) = source {
+        let mut inner = s;
+        while let Some(wrapped) = inner.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+            inner = wrapped;
+        }
+        if let Some(tls) = inner.downcast_ref::<rustls::Error>() {
+            return Some(tls);
+        }
+        source = s.source();
+    }
+    None
+}
+
+/// `e` for the last error, naming the TLS problem when the handshake
+/// failed: reqwest's own message only says the request could not be sent.
+fn failure_message(e: &reqwest::Error) -> String {
+    match tls_failure(e) {
+        Some(tls) => format!("{}: TLS handshake failed: {}", e, tls),
+        None => e.to_string(),
+    }
+}
+
+/// classify for an error from reading a streamed body, which reqwest wraps
+/// in io::Error.
+pub fn classify_io(e: &io::Error) -> i32 {
+    match e.get_ref().and_then(|inner| inner.downcast_ref::<reqwest::Error>()) {
+        Some(inner) => classify(inner),
+        None if e.kind() == io::ErrorKind::TimedOut => ERR_TIMEOUT,
+        None => ERR_REQUEST_FAILED,
+    }
+}
+
+/// Internal helper: attach headers and options to a RequestBuilder and build it.
+fn prepare(
+    builder: RequestBuilder,
     headers: HeaderMap,
-    timeout_ms: i32,
-) -> Result<HttpResponse, i32> {
+    options: &RequestOptions,
+) -> Result<Request, i32> {
+    // basic_auth and bearer_auth add an Authorization header rather than
+    // replacing one.
+    let mut headers = headers;
+    let builder = match (&options.basic_auth, &options.bearer_token) {
+        (Some((username, password)), _) => {
+            headers.remove(AUTHORIZATION);
+            builder.basic_auth(username, password.as_ref())
+        }
+        (None, Some(token)) => {
+            headers.remove(AUTHORIZATION);
+            builder.bearer_auth(token)
+        }
+        (None, None) => builder,
+    };
     let builder = builder.headers(headers);
 
-    let builder = if timeout_ms > 0 {
-        builder.timeout(Duration::from_millis(timeout_ms as u64))
+    // HTTP/1.0 servers cannot be relied on to keep connections open, so
+    // forcing 1.0 also closes the connection after every request.
+    let force_http10 = config::force_http10();
+    let builder = if force_http10 {
+        builder.version(Version::HTTP_10)
     } else {
         builder
     };
+    let keep_alive = if force_http10 { 0 } else { options.keep_alive };
 
-    let response = builder.send().map_err(|e| {
-        set_last_error(format!("Request failed: {}", e));
-        ERR_REQUEST_FAILED
-    })?;
+    let builder = match keep_alive {
+        0 => builder.header(CONNECTION, HeaderValue::from_static("close")),
+        1 => builder.header(CONNECTION, HeaderValue::from_static("keep-alive")),
+        _ => builder,
+    };
+
+    let mut timeout =
+        (options.timeout_ms > 0).then(|| Duration::from_millis(options.timeout_ms as u64));
+    if options.deadline_unix_millis > 0 {
+        let remaining_ms = options.deadline_unix_millis - date::now_unix_millis();
+        if remaining_ms <= 0 {
+            set_last_error(
+                ERR_TIMEOUT,
+                format!("Deadline already passed {} ms ago", -remaining_ms),
+            );
+            return Err(ERR_TIMEOUT);
+        }
+        let remaining = Duration::from_millis(remaining_ms as u64);
+        timeout = Some(timeout.map_or(remaining, |t| t.min(remaining)));
+    }
 
-    let status = response.status().as_u16() as u32;
+    let builder = match timeout {
+        Some(t) => builder.timeout(t),
+        None => builder,
+    };
 
-    let body = response.bytes().map_err(|e| {
-        set_last_error(format!("Failed to read response body: {}", e));
+    let request = builder.build().map_err(|e| {
+        set_last_error(ERR_REQUEST_FAILED, format!("Request failed: {}", e));
         ERR_REQUEST_FAILED
     })?;
+    if config::trace() {
+        trace::begin(&request);
+    } else {
+        trace::clear();
+    }
+    Ok(request)
+}
 
-    Ok(HttpResponse {
-        status,
-        body: body.to_vec(),
+/// Internal helper: send a built request, returning as soon as the response
+/// headers arrive. A request with a cancel token goes through cancel, so
+/// tripping the token aborts it; one whose body is streamed from a file can
+/// only be stopped before it is sent.
+fn dispatch(request: Request, options: &RequestOptions) -> Result<InFlight, i32> {
+    let method = request.method().clone();
+    let url = request.url().clone();
+
+    let client = get_client()?;
+    let tokens = cancel::active(options.cancel_token);
+    let cancellable = !tokens.is_empty() && cancel::can_execute(&request, options.force_chunked);
+    // Checked up front even when cancel::execute would catch it, so that a
+    // request that is never sent is not mirrored either.
+    cancel::check(&tokens)?;
+    mirror::mirror(&request);
+    let connect_retries = config::connect_retries();
+    let policy = config::retry_policy();
+    let max_retries = connect_retries.max(policy.max_retries);
+    let budget_ms = config::retry_budget_ms();
+    let started = Instant::now();
+    let budget_end = (budget_ms > 0).then(|| started + Duration::from_millis(budget_ms));
+    let mut request = request;
+    let mut attempt = 0;
+    let mut attempt_started = started;
+    let response = loop {
+        // No attempt may run past the end of the retry budget.
+        if let Some(end) = budget_end {
+            let remaining = end.saturating_duration_since(Instant::now());
+            let timeout = request.timeout().map_or(remaining, |t| (*t).min(remaining));
+            *request.timeout_mut() = Some(timeout);
+        }
+        // Keep a copy while retries remain; streaming bodies cannot be copied
+        // and so are never retried.
+        let spare = if attempt < max_retries { request.try_clone() } else { None };
+        let outcome = match cancellable {
+            true => cancel::execute(&tokens, request)?,
+            false => client.execute(request),
+        };
+        let delay = match (&outcome, &spare) {
+            (_, None) => None,
+            (Ok(response), Some(_)) => status_retry_delay(response, attempt, &policy),
+            (Err(e), Some(_)) => retry_delay(e, &method, attempt, connect_retries, &policy),
+        };
+        let (Some(delay), Some(spare)) = (delay, spare) else {
+            match outcome {
+                Ok(response) => break response,
+                Err(e) => {
+                    trace::note(&format!("request failed: {}", e));
+                    let code = classify(&e);
+                    let message = failure_message(&e);
+                    if attempt == 0 {
+                        set_last_error(code, format!("Request failed: {}", message));
+                    } else {
+                        set_last_error(
+                            code,
+                            format!("Request failed: {} (after {} attempts)", message, attempt + 1),
+                        );
+                    }
+                    return Err(code);
+                }
+            }
+        };
+        attempt += 1;
+        let reason = match &outcome {
+            Ok(response) => format!("status {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        trace::note(&format!(
+            "attempt {} failed: {}; retrying in {} ms",
+            attempt,
+            reason,
+            delay.as_millis()
+        ));
+        if budget_end.is_some_and(|end| Instant::now() + delay >= end) {
+            match outcome {
+                // The last response stands, with its real status.
+                Ok(response) => break response,
+                Err(e) => {
+                    let code = classify(&e);
+                    set_last_error(
+                        code,
+                        format!(
+                        "Request failed: {} (retry budget of {} ms exhausted after {} attempts)",
+                        failure_message(&e), budget_ms, attempt
+                    ),
+                    );
+                    return Err(code);
+                }
+            }
+        }
+        drop(outcome);
+        match cancellable {
+            true => cancel::sleep(&tokens, delay)?,
+            false => thread::sleep(delay),
+        }
+        request = spare;
+        attempt_started = Instant::now();
+    };
+    let ttfb = attempt_started.elapsed();
+    let setup = timing::take(&response);
+    trace::response(&response, negotiated_alpn(response.url(), response.version()));
+
+    Ok(InFlight {
+        method,
+        url,
+        started,
+        dns: setup.and_then(|t| t.dns),
+        connect: setup.map(|t| t.connect),
+        ttfb,
+        response,
     })
 }
 
-pub fn get(url: &str, headers: HeaderMap, timeout_ms: i32) -> Result<HttpResponse, i32> {
-    let client = get_client()?;
-    execute(client.get(url), headers, timeout_ms)
+/// How long to wait before retrying a request whose attempt number `attempt`
+/// (0-based) failed with `e`, or None if it must not be retried. Connect
+/// retries come first, at a fixed interval; after that the retry policy
+/// backs off exponentially.
+fn retry_delay(
+    e: &reqwest::Error,
+    method: &Method,
+    attempt: u32,
+    connect_retries: u32,
+    policy: &config::RetryPolicy,
+) -> Option<Duration> {
+    if e.is_connect() && attempt < connect_retries {
+        return Some(CONNECT_RETRY_DELAY);
+    }
+    // Nothing was sent if the connection failed. After a timeout the server
+    // may already have acted on the request, so only idempotent ones repeat.
+    let retryable = e.is_connect() || (e.is_timeout() && method.is_idempotent());
+    (retryable && attempt < policy.max_retries).then(|| {
+        let ms = backoff::ceiling_ms(attempt, policy.base_backoff_ms, MAX_RETRY_BACKOFF_MS);
+        Duration::from_millis(ms)
+    })
 }
 
-pub fn post(
+/// How long to wait before retrying a response whose status the retry policy
+/// lists, or None if it stands: the time its Retry-After header asks for, up
+/// to max_retry_after_ms, or the usual backoff if it has none.
+fn status_retry_delay(
+    response: &Response,
+    attempt: u32,
+    policy: &config::RetryPolicy,
+) -> Option<Duration> {
+    let status = response.status().as_u16();
+    if attempt >= policy.max_retries || !policy.retry_on_status.contains(&status) {
+        return None;
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| date::retry_after_ms(v, date::now_unix_millis()));
+    let ms = match retry_after {
+        Some(ms) => ms.min(policy.max_retry_after_ms),
+        None => backoff::ceiling_ms(attempt, policy.base_backoff_ms, MAX_RETRY_BACKOFF_MS),
+    };
+    Some(Duration::from_millis(ms))
+}
+
+/// Internal helper: attach headers and options, send, and return as soon as
+/// the response headers arrive.
+fn send(
+    builder: RequestBuilder,
+    headers: HeaderMap,
+    options: &RequestOptions,
+) -> Result<InFlight, i32> {
+    dispatch(prepare(builder, headers, options)?, options)
+}
+
+/// Internal helper: send a request and read the whole response body.
+/// Identical concurrent requests share one round trip when coalescing is on.
+pub fn execute(
+    builder: RequestBuilder,
+    headers: HeaderMap,
+    options: &RequestOptions,
+) -> Result<HttpResponse, i32> {
+    let request = prepare(builder, headers, options)?;
+    let max_body = options
+        .max_response_bytes
+        .unwrap_or_else(config::max_response_bytes);
+    if config::request_coalescing() {
+        if let Some(key) = coalesce::key(&request) {
+            return coalesce::run(key, || dispatch(request, options)?.finish(max_body));
+        }
+    }
+    dispatch(request, options)?.finish(max_body)
+}
+
+/// The reason phrase of `response`. hyper keeps the text only when it differs
+/// from the canonical reason, so otherwise the canonical one is what was sent.
+fn reason_phrase(response: &Response) -> String {
+    match response.extensions().get::<hyper::ext::ReasonPhrase>() {
+        Some(reason) => String::from_utf8_lossy(reason.as_bytes()).into_owned(),
+        None => response
+            .status()
+            .canonical_reason()
+            .unwrap_or("")
+            .to_string(),
+    }
+}
+
+/// The ALPN protocol implied by the HTTP version used on a TLS connection.
+/// The handshake's own result is not exposed by the client, so this is an
+/// inference: h2 can only be spoken over TLS once negotiated, while HTTP/1.x
+/// is reported as http/1.1 whether or not the server answered ALPN at all.
+fn negotiated_alpn(url: &reqwest::Url, version: Version) -> &'static str {
+    if url.scheme() != "https" {
+        return "";
+    }
+    match version {
+        Version::HTTP_2 => "h2",
+        Version::HTTP_10 | Version::HTTP_11 => "http/1.1",
+        _ => "",
+    }
+}
+
+/// Issue a request with an arbitrary method and per-request options.
+/// The body is attached if non-empty, and always for POST/PUT/PATCH so those
+/// send `Content-Length: 0` like the verb-specific helpers do.
+pub fn request(
+    method: Method,
     url: &str,
     headers: HeaderMap,
     body: Vec<u8>,
-    timeout_ms: i32,
+    options: &RequestOptions,
 ) -> Result<HttpResponse, i32> {
     let client = get_client()?;
-    execute(client.post(url).body(body), headers, timeout_ms)
+    let sends_body = matches!(method, Method::POST | Method::PUT | Method::PATCH);
+    let builder = client.request(method, url);
+    let builder = if options.force_chunked && !config::force_http10() {
+        // A body of unknown length makes hyper fall back to chunked encoding.
+        builder.body(Body::new(Cursor::new(body)))
+    } else if sends_body || !body.is_empty() {
+        builder.body(body)
+    } else {
+        builder
+    };
+    execute(builder, headers, options)
+}
+
+pub fn get(url: &str, headers: HeaderMap, timeout_ms: i32) -> Result<HttpResponse, i32> {
+    let options = RequestOptions::with_timeout(timeout_ms);
+    request(Method::GET, url, headers, Vec::new(), &options)
 }
 
-pub fn put(
+/// GET with `params` appended to the URL's query string, percent-encoded.
+/// Any query already in `url` is kept.
+pub fn get_with_params(
     url: &str,
     headers: HeaderMap,
-    body: Vec<u8>,
+    params: &[(String, String)],
     timeout_ms: i32,
 ) -> Result<HttpResponse, i32> {
     let client = get_client()?;
-    execute(client.put(url).body(body), headers, timeout_ms)
+    let builder = client.get(url).query(params);
+    execute(builder, headers, &RequestOptions::with_timeout(timeout_ms))
 }
 
-pub fn patch(
+/// GET that returns as soon as the response headers arrive, leaving the body
+/// unread so it can be consumed incrementally. Returns the status and the live
+/// response. Streamed requests are not recorded in the slow-request log.
+pub fn stream(url: &str, headers: HeaderMap, timeout_ms: i32) -> Result<(u32, Response), i32> {
+    let client = get_client()?;
+    let in_flight = send(client.get(url), headers, &RequestOptions::with_timeout(timeout_ms))?;
+    let status = in_flight.response.status().as_u16() as u32;
+    Ok((status, in_flight.response))
+}
+
+/// GET that must finish by an absolute deadline (Unix epoch milliseconds).
+pub fn get_until(
+    url: &str,
+    headers: HeaderMap,
+    deadline_unix_millis: i64,
+) -> Result<HttpResponse, i32> {
+    let options = RequestOptions {
+        deadline_unix_millis,
+        ..Default::default()
+    };
+    request(Method::GET, url, headers, Vec::new(), &options)
+}
+
+/// GET every URL concurrently on up to MAX_BATCH_THREADS threads, returning
+/// the results in the same order. Relative URLs resolve against the base URL.
+/// A failure carries its error code and message, since the last error is per
+/// thread.
+pub fn get_batch(
+    urls: &[String],
+    headers: &HeaderMap,
+    timeout_ms: i32,
+) -> Vec<Result<HttpResponse, (i32, String)>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<_>> = Mutex::new(urls.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..urls.len().min(MAX_BATCH_THREADS) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(url) = urls.get(i) else { break };
+                let result = config::resolve_url(url)
+                    .and_then(|url| get(&url, headers.clone(), timeout_ms))
+                    .map_err(|code| (code, last_error()));
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+pub fn post(
     url: &str,
     headers: HeaderMap,
     body: Vec<u8>,
     timeout_ms: i32,
 ) -> Result<HttpResponse, i32> {
-    let client = get_client()?;
-    execute(client.patch(url).body(body), headers, timeout_ms)
+    request(Method::POST, url, headers, body, &RequestOptions::with_timeout(timeout_ms))
 }
 
-pub fn delete(url: &str, headers: HeaderMap, timeout_ms: i32) -> Result<HttpResponse, i32> {
+/// POST `fields` as an `application/x-www-form-urlencoded` body, in order.
+pub fn post_form(
+    url: &str,
+    headers: HeaderMap,
+    fields: &[(String, String)],
+    timeout_ms: i32,
+) -> Result<HttpResponse, i32> {
     let client = get_client()?;
-    execute(client.delete(url), headers, timeout_ms)
+    let request = client.post(url).form(fields).build().map_err(|e| {
+        set_last_error(ERR_REQUEST_FAILED, format!("Request failed: {}", e));
+        ERR_REQUEST_FAILED
+    })?;
+    let body_len = request.body().and_then(|b| b.as_bytes()).map_or(0, <[u8]>::len);
+    config::check_request_size(body_len as u64)?;
+    let builder = RequestBuilder::from_parts((*client).clone(), request);
+    execute(builder, headers, &RequestOptions::with_timeout(timeout_ms))
+}
+
+/// POST text transcoded from UTF-8 to `charset`, with a Content-Type that
+/// names the charset. An existing Content-Type keeps its media type but has
+/// its charset replaced; otherwise `text/plain` is used.
+pub fn post_text(
+    url: &str,
+    mut headers: HeaderMap,
+    text: &str,
+    charset: &str,
+    timeout_ms: i32,
+) -> Result<HttpResponse, i32> {
+    let Some(charset) = Charset::from_label(charset) else {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Unknown charset: '{}'", charset));
+        return Err(ERR_INVALID_ARGUMENT);
+    };
+    let body = charset.encode(text).map_err(|c| {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!(
+                "Character '{}' (U+{:04X}) cannot be encoded as {}",
+                c,
+                c as u32,
+                charset.name()
+            ),
+        );
+        ERR_INVALID_ARGUMENT
+    })?;
+
+    let media_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| "text/plain".to_string());
+    let content_type = format!("{}; charset={}", media_type, charset.name());
+    let content_type = HeaderValue::from_str(&content_type).map_err(|e| {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Invalid Content-Type '{}': {}", content_type, e),
+        );
+        ERR_INVALID_ARGUMENT
+    })?;
+    headers.insert(CONTENT_TYPE, content_type);
+
+    crate::config::check_request_size(body.len() as u64)?;
+    post(url, headers, body, timeout_ms)
+}
+
+/// GET tuned for long-polling: the timeout covers the server's hold period
+/// plus slack. A non-positive hold means no client-side timeout at all.
+pub fn long_poll(url: &str, headers: HeaderMap, hold_timeout_ms: i32) -> Result<HttpResponse, i32> {
+    let timeout_ms = if hold_timeout_ms > 0 {
+        hold_timeout_ms.saturating_add(LONG_POLL_SLACK_MS)
+    } else {
+        0
+    };
+    get(url, headers, timeout_ms)
+}
+
+/// Repeatedly GET `url` until the JSON value at `json_path` equals `expected`,
+/// or until `deadline_ms` has elapsed (ERR_TIMEOUT). Transport errors, non-2xx
+/// statuses and unparseable bodies are treated as "not yet" and polled again.
+/// On success the matching response is returned. `interval_ms` must be
+/// positive, so a failing endpoint is not hammered in a tight loop.
+pub fn poll_until(
+    url: &str,
+    headers: HeaderMap,
+    json_path: &str,
+    expected: &str,
+    interval_ms: i32,
+    deadline_ms: i32,
+) -> Result<HttpResponse, i32> {
+    if interval_ms <= 0 {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Poll interval must be positive: {} ms", interval_ms),
+        );
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+    let started = Instant::now();
+    let deadline = Duration::from_millis(deadline_ms.max(0) as u64);
+    let interval = Duration::from_millis(interval_ms as u64);
+    let mut attempts = 0u32;
+
+    loop {
+        let remaining = deadline.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        attempts += 1;
+        let timeout_ms = remaining.as_millis().clamp(1, i32::MAX as u128) as i32;
+        if let Ok(resp) = get(url, headers.clone(), timeout_ms) {
+            let matched = (200..300).contains(&resp.status)
+                && serde_json::from_slice::<serde_json::Value>(&resp.body)
+                    .ok()
+                    .and_then(|v| {
+                        jsonpath::extract(&v, json_path).map(|f| jsonpath::matches(f, expected))
+                    })
+                    .unwrap_or(false);
+            if matched {
+                return Ok(resp);
+            }
+        }
+        let remaining = deadline.saturating_sub(started.elapsed());
+        std::thread::sleep(interval.min(remaining));
+    }
+
+    set_last_error(
+        ERR_TIMEOUT,
+        format!(
+            "Timed out after {} ms waiting for '{}' to equal '{}' ({} attempts)",
+            deadline_ms, json_path, expected, attempts
+        ),
+    );
+    Err(ERR_TIMEOUT)
 }
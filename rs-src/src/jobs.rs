@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{
+    clear_last_error, last_error, set_last_error, ERR_INVALID_HANDLE, ERR_REQUEST_FAILED,
+};
+use crate::http::HttpResponse;
+use crate::{cancel, workers};
+
+/// A failed request's error code and message, carried over from the worker
+/// thread's last error.
+pub type Failure = (i32, String);
+
+enum Job {
+    /// Carries the token that cancel trips to abort the request.
+    Running(u64),
+    Finished(Box<Result<HttpResponse, Failure>>),
+}
+
+static JOBS: OnceLock<Mutex<HashMap<u64, Job>>> = OnceLock::new();
+
+// Starts at 1 so that 0 can serve as a sentinel "no handle" value in LabVIEW
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+fn jobs() -> &'static Mutex<HashMap<u64, Job>> {
+    JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Run `request` on a background thread and return its request handle. The
+/// request runs with a token of its own, so cancel can abort it.
+pub fn submit(
+    request: impl FnOnce() -> Result<HttpResponse, i32> + Send + 'static,
+) -> Result<u64, i32> {
+    let handle = NEXT_JOB.fetch_add(1, Ordering::Relaxed);
+    let token = cancel::new_token();
+    jobs().lock().unwrap().insert(handle, Job::Running(token));
+    let started = workers::spawn("async", move || {
+        let result = cancel::scoped(token, request).map_err(|code| (code, last_error()));
+        let _ = cancel::free(token);
+        // Nobody is waiting if the handle was dropped in the meantime.
+        if let Some(job) = jobs().lock().unwrap().get_mut(&handle) {
+            *job = Job::Finished(Box::new(result));
+        }
+    });
+    if let Err(e) = started {
+        jobs().lock().unwrap().remove(&handle);
+        let _ = cancel::free(token);
+        set_last_error(ERR_REQUEST_FAILED, format!("Failed to start request thread: {}", e));
+        return Err(ERR_REQUEST_FAILED);
+    }
+    Ok(handle)
+}
+
+/// The outcome of a request without waiting for it: None while it is still
+/// running, otherwise its result, which forgets the handle.
+pub fn poll(handle: u64) -> Result<Option<Result<HttpResponse, Failure>>, i32> {
+    let mut jobs = jobs().lock().unwrap();
+    match jobs.remove(&handle) {
+        None => {
+            set_last_error(
+                ERR_INVALID_HANDLE,
+                format!("Invalid or already-completed request handle: {}", handle),
+            );
+            Err(ERR_INVALID_HANDLE)
+        }
+        Some(Job::Running(token)) => {
+            jobs.insert(handle, Job::Running(token));
+            Ok(None)
+        }
+        Some(Job::Finished(result)) => Ok(Some(*result)),
+    }
+}
+
+/// Forget a request, discarding its result. Returns true if it was still
+/// running, in which case it is aborted and its connection closed, false if
+/// it had already finished.
+pub fn cancel(handle: u64) -> Result<bool, i32> {
+    match jobs().lock().unwrap().remove(&handle) {
+        None => {
+            set_last_error(
+                ERR_INVALID_HANDLE,
+                format!("Invalid or already-completed request handle: {}", handle),
+            );
+            Err(ERR_INVALID_HANDLE)
+        }
+        Some(Job::Running(token)) => {
+            // The worker may free the token as it finishes.
+            if cancel::cancel(token).is_err() {
+                clear_last_error();
+            }
+            Ok(true)
+        }
+        Some(Job::Finished(_)) => Ok(false),
+    }
+}
+
+/// Forget every request; results of those still running are discarded.
+/// Called from http_shutdown.
+pub fn clear_all() {
+    jobs().lock().unwrap().clear();
+}
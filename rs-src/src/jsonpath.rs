@@ -0,0 +1,69 @@
+use serde_json::Value;
+
+/// Look up a value by a simple path.
+///
+/// Accepts dotted paths with optional array indices (`state`, `job.steps[2].name`,
+/// optionally prefixed with `$.`), or an RFC 6901 JSON Pointer (`/job/steps/2/name`).
+pub fn extract<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.starts_with('/') {
+        return root.pointer(path);
+    }
+
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let path = path.strip_prefix('.').unwrap_or(path);
+    let mut current = root;
+
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (name, mut indices) = match segment.find('[') {
+            Some(i) => (&segment[..i], &segment[i..]),
+            None => (segment, ""),
+        };
+        if !name.is_empty() {
+            current = current.get(name)?;
+        }
+        while let Some(rest) = indices.strip_prefix('[') {
+            let end = rest.find(']')?;
+            let index: usize = rest[..end].trim().parse().ok()?;
+            current = current.get(index)?;
+            indices = &rest[end + 1..];
+        }
+        if !indices.is_empty() {
+            return None;
+        }
+    }
+    Some(current)
+}
+
+/// Compare an extracted value with an expected value given as text.
+/// Strings compare by content (`complete` matches "complete"); other values
+/// compare as JSON, so `true`, `3` and `null` match their JSON counterparts.
+pub fn matches(value: &Value, expected: &str) -> bool {
+    match value {
+        Value::String(s) => s == expected,
+        other => serde_json::from_str::<Value>(expected)
+            .map(|e| &e == other)
+            .unwrap_or(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn extracts_dotted_paths_and_pointers() {
+        let doc = json!({"job": {"steps": [{"status": "ok"}, {"status": "running"}]}});
+        assert_eq!(extract(&doc, "job.steps[1].status"), Some(&json!("running")));
+        assert_eq!(extract(&doc, "/job/steps/0/status"), Some(&json!("ok")));
+        assert_eq!(extract(&doc, "job.missing"), None);
+    }
+
+    #[test]
+    fn matches_strings_by_content_and_others_as_json() {
+        assert!(matches(&json!("done"), "done"));
+        assert!(matches(&json!(3), "3"));
+        assert!(matches(&json!(true), "true"));
+        assert!(!matches(&json!(3), "\"3\""));
+    }
+}
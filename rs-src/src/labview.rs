@@ -0,0 +1,75 @@
+// LabVIEW native data types, for functions that take LabVIEW handles directly
+// instead of flat pointers. Only built with the `labview` feature.
+//
+// A LabVIEW array handle is a pointer to a pointer to a block that starts with
+// one i32 size per dimension, followed immediately by the elements in
+// row-major order. LabVIEW owns the memory; we only read it during the call.
+
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT};
+
+/// Header of a 2D array of U8. `data` is the first element; the rest follow.
+#[repr(C)]
+pub struct LvU8Array2D {
+    pub dims: [i32; 2],
+    pub data: [u8; 1],
+}
+
+pub type LvU8Array2DHandle = *mut *mut LvU8Array2D;
+
+/// Concatenate the rows of a 2D U8 array into one buffer.
+///
+/// A null handle, or an array with zero rows or columns, gives an empty body.
+///
+/// # Safety
+/// `handle` must be null or a valid LabVIEW 2D U8 array handle.
+pub unsafe fn array2d_rows(handle: LvU8Array2DHandle) -> Result<Vec<u8>, i32> {
+    if handle.is_null() || (*handle).is_null() {
+        return Ok(Vec::new());
+    }
+    let array = *handle;
+    let [rows, cols] = (*array).dims;
+    if rows < 0 || cols < 0 {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid array dimensions {}x{}", rows, cols));
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+    let len = (rows as usize).checked_mul(cols as usize).ok_or_else(|| {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Array of {}x{} bytes is too large", rows, cols),
+        );
+        ERR_INVALID_ARGUMENT
+    })?;
+    let data = std::ptr::addr_of!((*array).data) as *const u8;
+    Ok(std::slice::from_raw_parts(data, len).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concatenates_rows() {
+        // dims (2 rows x 3 cols) followed by the elements, as LabVIEW lays it out.
+        let mut block = [0u32; 4];
+        block[0] = 2;
+        block[1] = 3;
+        let bytes = block.as_mut_ptr() as *mut u8;
+        unsafe {
+            std::ptr::copy_nonoverlapping([1u8, 2, 3, 4, 5, 6].as_ptr(), bytes.add(8), 6);
+            let mut array = block.as_mut_ptr() as *mut LvU8Array2D;
+            assert_eq!(array2d_rows(&mut array).unwrap(), [1, 2, 3, 4, 5, 6]);
+        }
+    }
+
+    #[test]
+    fn empty_and_invalid_arrays() {
+        let mut block = [0i32, 5, 0, 0];
+        let mut array = block.as_mut_ptr() as *mut LvU8Array2D;
+        unsafe {
+            assert!(array2d_rows(std::ptr::null_mut()).unwrap().is_empty());
+            assert!(array2d_rows(&mut array).unwrap().is_empty());
+            (*array).dims[0] = -1;
+            assert_eq!(array2d_rows(&mut array), Err(ERR_INVALID_ARGUMENT));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT};
+use crate::http::{self, HttpResponse};
+
+/// Value of an attribute inside a single tag, e.g. `content` in
+/// `<meta http-equiv="refresh" content="0; url=/next">`. Matching is
+/// case-insensitive; quoted and bare values are supported.
+fn attribute<'a>(tag: &'a str, tag_lower: &str, name: &str) -> Option<&'a str> {
+    let mut search_from = 0;
+    while let Some(pos) = tag_lower[search_from..].find(name) {
+        let start = search_from + pos;
+        search_from = start + name.len();
+        // Must be a whole attribute name, not the tail of another one
+        let before = tag_lower[..start].chars().last();
+        if !before.is_some_and(|c| c.is_whitespace()) {
+            continue;
+        }
+        let rest = tag[search_from..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        return match rest.chars().next() {
+            Some(q @ ('"' | '\'')) => rest[1..].split(q).next(),
+            _ => rest.split(|c: char| c.is_whitespace() || c == '>').next(),
+        };
+    }
+    None
+}
+
+/// Extract the target URL of the first `<meta http-equiv="refresh">` tag.
+/// A refresh without a URL (a plain reload) yields None.
+fn find_meta_refresh(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(pos) = lower[offset..].find("<meta") {
+        let start = offset + pos;
+        let end = lower[start..].find('>').map_or(lower.len(), |e| start + e);
+        let (tag, tag_lower) = (&html[start..end], &lower[start..end]);
+        offset = end;
+
+        let is_refresh = attribute(tag, tag_lower, "http-equiv")
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("refresh"));
+        if !is_refresh {
+            continue;
+        }
+        // content="<seconds>; url=<target>"
+        let content = attribute(tag, tag_lower, "content")?;
+        let (_, target) = content.split_once([';', ','])?;
+        let target = target.trim();
+        let target = if target.len() >= 4 && target[..4].eq_ignore_ascii_case("url=") {
+            &target[4..]
+        } else {
+            target
+        };
+        let target = target.trim().trim_matches(|c| c == '"' || c == '\'');
+        return (!target.is_empty()).then(|| target.to_string());
+    }
+    None
+}
+
+fn is_html(resp: &HttpResponse) -> bool {
+    resp.headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            let v = v.to_ascii_lowercase();
+            v.starts_with("text/html") || v.starts_with("application/xhtml+xml")
+        })
+        .unwrap_or(false)
+}
+
+/// GET `url`, following HTML meta-refresh redirects up to `max_hops` times.
+/// Responses that are not HTML, or have no meta refresh, are returned as-is.
+pub fn get_follow_meta_refresh(
+    url: &str,
+    headers: HeaderMap,
+    max_hops: i32,
+    timeout_ms: i32,
+) -> Result<HttpResponse, i32> {
+    if max_hops < 0 {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("max_hops must not be negative, got {}", max_hops),
+        );
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+
+    let mut resp = http::get(url, headers.clone(), timeout_ms)?;
+    for _ in 0..max_hops {
+        if !is_html(&resp) {
+            break;
+        }
+        let Some(target) = find_meta_refresh(&String::from_utf8_lossy(&resp.body)) else {
+            break;
+        };
+        // Relative targets resolve against the URL that served the page
+        let next = resp.url.join(&target).map_err(|e| {
+            set_last_error(
+                ERR_INVALID_ARGUMENT,
+                format!("Invalid meta refresh URL '{}': {}", target, e),
+            );
+            ERR_INVALID_ARGUMENT
+        })?;
+        resp = http::get(next.as_str(), headers.clone(), timeout_ms)?;
+    }
+    Ok(resp)
+}
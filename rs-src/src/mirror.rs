@@ -0,0 +1,74 @@
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::blocking::Request;
+use reqwest::{Method, Url};
+
+use crate::runtime::get_client;
+use crate::workers;
+
+/// Where and which requests to duplicate.
+struct MirrorConfig {
+    base: Url,
+    methods: Vec<Method>,
+}
+
+static MIRROR: OnceLock<Mutex<Option<MirrorConfig>>> = OnceLock::new();
+
+fn mirror_config() -> &'static Mutex<Option<MirrorConfig>> {
+    MIRROR.get_or_init(|| Mutex::new(None))
+}
+
+/// Enable mirroring of `methods` to `base`, or disable it with None.
+pub fn set_mirror(config: Option<(Url, Vec<Method>)>) {
+    *mirror_config().lock().unwrap() =
+        config.map(|(base, methods)| MirrorConfig { base, methods });
+}
+
+/// The current mirror settings as JSON (null when mirroring is off).
+pub fn to_json() -> serde_json::Value {
+    match mirror_config().lock().unwrap().as_ref() {
+        Some(config) => serde_json::json!({
+            "shadow_base_url": config.base.as_str(),
+            "methods": config.methods.iter().map(Method::as_str).collect::<Vec<_>>(),
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Rebase `url` onto the shadow host, keeping its path (after any base path) and query.
+fn shadow_url(base: &Url, url: &Url) -> Url {
+    let mut shadow = base.clone();
+    let path = format!("{}{}", base.path().trim_end_matches('/'), url.path());
+    shadow.set_path(&path);
+    shadow.set_query(url.query());
+    shadow
+}
+
+/// If mirroring is configured for this request's method, send a copy to the
+/// shadow host on a background thread. The shadow's response is ignored.
+///
+/// Requests with streaming bodies cannot be cloned and are not mirrored.
+pub fn mirror(request: &Request) {
+    let shadow = {
+        let config = mirror_config().lock().unwrap();
+        let Some(config) = config.as_ref() else {
+            return;
+        };
+        if !config.methods.contains(request.method()) {
+            return;
+        }
+        let Some(mut copy) = request.try_clone() else {
+            return;
+        };
+        *copy.url_mut() = shadow_url(&config.base, request.url());
+        copy
+    };
+
+    // Fire and forget: a failure to send (or to spawn) must never affect the
+    // primary request.
+    let _ = workers::spawn("mirror", move || {
+        if let Ok(client) = get_client() {
+            let _ = client.execute(shadow);
+        }
+    });
+}
@@ -0,0 +1,582 @@
+use std::ffi::CStr;
+use std::net::IpAddr;
+use std::os::raw::c_char;
+
+use reqwest::header::HeaderMap;
+
+use crate::config::{CertVerification, ClientConfig, RetryPolicy, TlsVersion};
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT, ERR_INVALID_UTF8};
+use crate::headers::header_map_from_json;
+
+/// Per-request settings for http_request_ex, passed as a JSON object such as
+/// {"timeout_ms": 5000, "keep_alive": 0}. Every field is optional.
+#[derive(Clone, Debug)]
+pub struct RequestOptions {
+    /// Per-request timeout in milliseconds; <= 0 means no timeout.
+    pub timeout_ms: i32,
+    /// Connection behaviour: -1 client default, 0 send `Connection: close`,
+    /// 1 send `Connection: keep-alive`.
+    pub keep_alive: i32,
+    /// Absolute deadline as Unix epoch milliseconds; 0 means none. The request
+    /// timeout is shortened to the time remaining when the request starts.
+    pub deadline_unix_millis: i64,
+    /// Send the body with `Transfer-Encoding: chunked` instead of
+    /// Content-Length, for intermediaries that mishandle the latter.
+    pub force_chunked: bool,
+    /// Username and optional password for HTTP Basic authentication. Replaces
+    /// any Authorization header given in the headers JSON.
+    pub basic_auth: Option<(String, Option<String>)>,
+    /// Token for `Authorization: Bearer <token>`, likewise replacing any
+    /// Authorization header. Cannot be combined with basic_auth.
+    pub bearer_token: Option<String>,
+    /// Largest response body to read, overriding the http_init limit for this
+    /// request; 0 means no limit.
+    pub max_response_bytes: Option<u64>,
+    /// Token from http_new_cancel_token that aborts the request when tripped.
+    pub cancel_token: Option<u64>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        RequestOptions {
+            timeout_ms: 0,
+            keep_alive: -1,
+            deadline_unix_millis: 0,
+            force_chunked: false,
+            basic_auth: None,
+            bearer_token: None,
+            max_response_bytes: None,
+            cancel_token: None,
+        }
+    }
+}
+
+impl RequestOptions {
+    /// Default options with just a timeout, as used by the verb-specific functions.
+    pub fn with_timeout(timeout_ms: i32) -> Self {
+        RequestOptions {
+            timeout_ms,
+            ..Default::default()
+        }
+    }
+}
+
+fn as_bool(key: &str, value: &serde_json::Value) -> Result<bool, i32> {
+    value.as_bool().ok_or_else(|| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Option '{}' must be true or false", key));
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+fn as_i64(key: &str, value: &serde_json::Value) -> Result<i64, i32> {
+    value.as_i64().ok_or_else(|| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Option '{}' must be an integer", key));
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+fn as_i32(key: &str, value: &serde_json::Value) -> Result<i32, i32> {
+    value
+        .as_i64()
+        .and_then(|v| i32::try_from(v).ok())
+        .ok_or_else(|| {
+            set_last_error(
+                ERR_INVALID_ARGUMENT,
+                format!("Option '{}' must be a 32-bit integer", key),
+            );
+            ERR_INVALID_ARGUMENT
+        })
+}
+
+fn as_string(key: &str, value: &serde_json::Value) -> Result<String, i32> {
+    value.as_str().map(str::to_string).ok_or_else(|| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Option '{}' must be a string", key));
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+/// Parse `dns_overrides`: {"host" or "host:port": "IP" or ["IP", ...]}.
+/// Hosts are lowercased. Two keys for one host must give the same addresses,
+/// since overrides apply to the host whatever the port.
+fn dns_overrides_from_json(
+    map: serde_json::Map<String, serde_json::Value>,
+) -> Result<Vec<(String, Vec<IpAddr>)>, i32> {
+    let invalid = |msg: String| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid 'dns_overrides': {}", msg));
+        ERR_INVALID_ARGUMENT
+    };
+    let mut overrides: Vec<(String, Vec<IpAddr>)> = Vec::new();
+    for (key, value) in map {
+        let host = match key.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => host,
+            Some(_) => return Err(invalid(format!("bad port in '{}'", key))),
+            None => key.as_str(),
+        };
+        if host.is_empty() || host.parse::<IpAddr>().is_ok() || host.contains(['/', '[', ']']) {
+            return Err(invalid(format!("'{}' must be a host name, optionally with :port", key)));
+        }
+        let values = match &value {
+            serde_json::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        let mut ips = Vec::new();
+        for value in values {
+            match value.as_str().map(str::parse::<IpAddr>) {
+                Some(Ok(ip)) => ips.push(ip),
+                _ => return Err(invalid(format!("'{}' maps to {}, not an IP address", key, value))),
+            }
+        }
+        if ips.is_empty() {
+            return Err(invalid(format!("'{}' has no addresses", key)));
+        }
+        let host = host.to_ascii_lowercase();
+        match overrides.iter().find(|(h, _)| *h == host) {
+            Some((_, existing)) if *existing != ips => {
+                return Err(invalid(format!("conflicting addresses for host '{}'", host)));
+            }
+            Some(_) => {}
+            None => overrides.push((host, ips)),
+        }
+    }
+    Ok(overrides)
+}
+
+fn as_u64(key: &str, value: &serde_json::Value) -> Result<u64, i32> {
+    value.as_u64().ok_or_else(|| {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Option '{}' must be a non-negative integer", key),
+        );
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+/// Read a null-terminated JSON object; a null pointer or empty string is None.
+fn parse_object(
+    json: *const c_char,
+    what: &str,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>, i32> {
+    if json.is_null() {
+        return Ok(None);
+    }
+    let json_str = unsafe { CStr::from_ptr(json) }.to_str().map_err(|_| {
+        set_last_error(ERR_INVALID_UTF8, format!("{} JSON string contains invalid UTF-8", what));
+        ERR_INVALID_UTF8
+    })?;
+    if json_str.trim().is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(json_str).map(Some).map_err(|e| {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Failed to parse {} JSON: {}", what.to_lowercase(), e),
+        );
+        ERR_INVALID_ARGUMENT
+    })
+}
+
+/// Client settings for http_init, passed as a JSON object such as
+/// {"user_agent": "rig-7/1.0", "pool_max_idle_per_host": 4}. Every field is
+/// optional; absent fields keep the current client configuration.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInit {
+    pub user_agent: Option<String>,
+    pub default_headers: Option<HeaderMap>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout_ms: Option<u64>,
+    pub tcp_nodelay: Option<bool>,
+    pub tcp_keepalive_ms: Option<u64>,
+    pub disable_keepalive: Option<bool>,
+    pub http1_only: Option<bool>,
+    pub ca_cert_path: Option<std::path::PathBuf>,
+    pub client_identity: Option<(std::path::PathBuf, std::path::PathBuf)>,
+    pub danger_accept_invalid_certs: Option<bool>,
+    pub danger_accept_invalid_hostnames: Option<bool>,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub no_proxy_from_env: Option<bool>,
+    pub dns_overrides: Option<Vec<(String, Vec<IpAddr>)>>,
+    pub tls_min_version: Option<TlsVersion>,
+    pub tls_max_version: Option<TlsVersion>,
+    pub max_retries: Option<u32>,
+    pub base_backoff_ms: Option<u64>,
+    pub retry_on_status: Option<Vec<u16>>,
+    pub max_retry_after_ms: Option<u64>,
+    pub max_response_bytes: Option<u64>,
+    pub max_stored_responses: Option<usize>,
+}
+
+impl ClientInit {
+    /// `current` with the retry fields that were given replaced.
+    pub fn retry_policy(&self, current: RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(current.max_retries),
+            base_backoff_ms: self.base_backoff_ms.unwrap_or(current.base_backoff_ms),
+            retry_on_status: self.retry_on_status.clone().unwrap_or(current.retry_on_status),
+            max_retry_after_ms: self.max_retry_after_ms.unwrap_or(current.max_retry_after_ms),
+        }
+    }
+
+    /// Overwrite the fields that were given.
+    pub fn apply_to(self, config: &mut ClientConfig) {
+        let current = config.cert_verification;
+        config.cert_verification = CertVerification::from_flags(
+            self.danger_accept_invalid_certs
+                .unwrap_or(current.accepts_invalid_certs()),
+            self.danger_accept_invalid_hostnames
+                .unwrap_or(current.accepts_invalid_hostnames()),
+        );
+        if let Some(agent) = self.user_agent {
+            config.user_agent = Some(agent);
+        }
+        if let Some(headers) = self.default_headers {
+            config.default_headers = headers;
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            config.pool_max_idle_per_host = Some(max);
+        }
+        if let Some(ms) = self.pool_idle_timeout_ms {
+            config.pool_idle_timeout_ms = Some(ms);
+        }
+        if let Some(nodelay) = self.tcp_nodelay {
+            config.tcp_nodelay = nodelay;
+        }
+        if let Some(ms) = self.tcp_keepalive_ms {
+            config.tcp_keepalive_ms = ms;
+        }
+        if let Some(disable) = self.disable_keepalive {
+            config.disable_keepalive = disable;
+        }
+        if let Some(http1_only) = self.http1_only {
+            config.http1_only = http1_only;
+        }
+        if let Some(path) = self.ca_cert_path {
+            config.ca_cert_path = Some(path);
+        }
+        if let Some(identity) = self.client_identity {
+            config.client_identity = Some(identity);
+        }
+        if let Some(proxy) = self.http_proxy {
+            config.http_proxy = Some(proxy);
+        }
+        if let Some(proxy) = self.https_proxy {
+            config.https_proxy = Some(proxy);
+        }
+        if let Some(no_proxy) = self.no_proxy {
+            config.no_proxy = Some(no_proxy);
+        }
+        if let Some(no_env) = self.no_proxy_from_env {
+            config.no_proxy_from_env = no_env;
+        }
+        if let Some(overrides) = self.dns_overrides {
+            config.dns_overrides = overrides;
+        }
+        if let Some(version) = self.tls_min_version {
+            config.tls_min_version = Some(version);
+        }
+        if let Some(version) = self.tls_max_version {
+            config.tls_max_version = Some(version);
+        }
+    }
+}
+
+/// Parse a null-terminated JSON client configuration for http_init. A null
+/// pointer or empty string gives no changes; unknown keys are rejected.
+pub fn parse_client_init(config_json: *const c_char) -> Result<ClientInit, i32> {
+    let mut init = ClientInit::default();
+    let Some(map) = parse_object(config_json, "Client config")? else {
+        return Ok(init);
+    };
+    let mut client_cert_path = None;
+    let mut client_key_path = None;
+
+    for (key, value) in map {
+        match key.as_str() {
+            "user_agent" => {
+                let agent = value.as_str().ok_or_else(|| {
+                    set_last_error(ERR_INVALID_ARGUMENT, "Option 'user_agent' must be a string");
+                    ERR_INVALID_ARGUMENT
+                })?;
+                if reqwest::header::HeaderValue::from_str(agent).is_err() {
+                    set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid user agent '{}'", agent));
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+                init.user_agent = Some(agent.to_string());
+            }
+            "default_headers" => {
+                let serde_json::Value::Object(headers) = value else {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "Option 'default_headers' must be an object",
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                };
+                init.default_headers = Some(header_map_from_json(headers)?);
+            }
+            "pool_max_idle_per_host" => {
+                init.pool_max_idle_per_host = Some(as_u64(&key, &value)? as usize)
+            }
+            "pool_idle_timeout_ms" => init.pool_idle_timeout_ms = Some(as_u64(&key, &value)?),
+            "tcp_nodelay" => init.tcp_nodelay = Some(as_bool(&key, &value)?),
+            "tcp_keepalive_ms" => init.tcp_keepalive_ms = Some(as_u64(&key, &value)?),
+            "disable_keepalive" => init.disable_keepalive = Some(as_bool(&key, &value)?),
+            "http_version" => match as_string(&key, &value)?.as_str() {
+                "auto" => init.http1_only = Some(false),
+                "http1" => init.http1_only = Some(true),
+                // reqwest's http2 feature (and its h2 dependency) is not part
+                // of this build, so neither negotiation nor prior knowledge
+                // can reach an HTTP/2-only server.
+                "http2" => {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "HTTP/2 is not available in this build: it lacks reqwest's http2 \
+                         feature; use \"auto\" or \"http1\"",
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+                other => {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!(
+                            "Option 'http_version' must be \"auto\", \"http1\" or \"http2\", \
+                             not '{}'",
+                            other
+                        ),
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+            },
+            "ca_cert_path" => init.ca_cert_path = Some(as_string(&key, &value)?.into()),
+            "client_cert_path" => client_cert_path = Some(as_string(&key, &value)?),
+            "client_key_path" => client_key_path = Some(as_string(&key, &value)?),
+            "danger_accept_invalid_certs" => {
+                init.danger_accept_invalid_certs = Some(as_bool(&key, &value)?)
+            }
+            "danger_accept_invalid_hostnames" => {
+                init.danger_accept_invalid_hostnames = Some(as_bool(&key, &value)?)
+            }
+            "http_proxy" | "https_proxy" => {
+                let url = as_string(&key, &value)?;
+                if let Err(e) = reqwest::Proxy::all(url.as_str()) {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!("Invalid proxy URL '{}': {}", url, e),
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+                // reqwest's socks feature (and its tokio-socks dependency) is
+                // not part of this build, so SOCKS URLs could never connect.
+                if url.trim_start().to_ascii_lowercase().starts_with("socks") {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!(
+                            "SOCKS proxy '{}' is not supported: this build lacks reqwest's \
+                         socks feature; use an HTTP proxy",
+                            url
+                        ),
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+                if key == "http_proxy" {
+                    init.http_proxy = Some(url);
+                } else {
+                    init.https_proxy = Some(url);
+                }
+            }
+            "no_proxy" => init.no_proxy = Some(as_string(&key, &value)?),
+            "no_proxy_from_env" | "use_env_proxy" => {
+                let ignore_env = as_bool(&key, &value)? == (key == "no_proxy_from_env");
+                if init.no_proxy_from_env.is_some_and(|current| current != ignore_env) {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "Options 'use_env_proxy' and 'no_proxy_from_env' contradict each other",
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+                init.no_proxy_from_env = Some(ignore_env);
+            }
+            "tls_min_version" | "tls_max_version" => {
+                let name = as_string(&key, &value)?;
+                let Some(version) = TlsVersion::parse(&name) else {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!(
+                            "Option '{}' must be \"1.2\" or \"1.3\", not '{}' (older TLS \
+                             versions are not supported)",
+                            key, name
+                        ),
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                };
+                if key == "tls_min_version" {
+                    init.tls_min_version = Some(version);
+                } else {
+                    init.tls_max_version = Some(version);
+                }
+            }
+            "dns_overrides" => {
+                let serde_json::Value::Object(map) = value else {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "Option 'dns_overrides' must be an object",
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                };
+                init.dns_overrides = Some(dns_overrides_from_json(map)?);
+            }
+            "max_retries" => {
+                let n = as_u64(&key, &value)?;
+                init.max_retries = Some(u32::try_from(n).map_err(|_| {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!("Option 'max_retries' is too large: {}", n),
+                    );
+                    ERR_INVALID_ARGUMENT
+                })?)
+            }
+            "base_backoff_ms" => init.base_backoff_ms = Some(as_u64(&key, &value)?),
+            "retry_on_status" => {
+                let statuses = value.as_array().and_then(|values| {
+                    values
+                        .iter()
+                        .map(|v| v.as_u64().filter(|s| (100..=599).contains(s)))
+                        .map(|s| s.map(|s| s as u16))
+                        .collect::<Option<Vec<u16>>>()
+                });
+                init.retry_on_status = Some(statuses.ok_or_else(|| {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "Option 'retry_on_status' must be an array of HTTP statuses",
+                    );
+                    ERR_INVALID_ARGUMENT
+                })?)
+            }
+            "max_retry_after_ms" => init.max_retry_after_ms = Some(as_u64(&key, &value)?),
+            "max_response_bytes" => init.max_response_bytes = Some(as_u64(&key, &value)?),
+            "max_stored_responses" => {
+                init.max_stored_responses = Some(as_u64(&key, &value)? as usize)
+            }
+            // reqwest's gzip, brotli and deflate features are not compiled in, so
+            // bodies are only ever stored as sent.
+            "auto_decompress" => {
+                if as_bool(&key, &value)? {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        "Automatic decompression is not available in this build; \
+                         bodies are always returned as sent",
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+            }
+            _ => {
+                set_last_error(ERR_INVALID_ARGUMENT, format!("Unknown client option '{}'", key));
+                return Err(ERR_INVALID_ARGUMENT);
+            }
+        }
+    }
+
+    if let (Some(min), Some(max)) = (init.tls_min_version, init.tls_max_version) {
+        if min > max {
+            set_last_error(
+                ERR_INVALID_ARGUMENT,
+                format!(
+                    "Option 'tls_min_version' ({}) is newer than 'tls_max_version' ({})",
+                    min.name(),
+                    max.name()
+                ),
+            );
+            return Err(ERR_INVALID_ARGUMENT);
+        }
+    }
+
+    init.client_identity = match (client_cert_path, client_key_path) {
+        (Some(cert), Some(key)) => Some((cert.into(), key.into())),
+        (None, None) => None,
+        _ => {
+            set_last_error(
+                ERR_INVALID_ARGUMENT,
+                "Options 'client_cert_path' and 'client_key_path' go together",
+            );
+            return Err(ERR_INVALID_ARGUMENT);
+        }
+    };
+
+    Ok(init)
+}
+
+/// Parse a null-terminated JSON options object into RequestOptions.
+///
+/// A null pointer or empty string gives the defaults. Unknown keys are rejected
+/// so that a typo does not silently fall back to default behaviour.
+pub fn parse_options(options_json: *const c_char) -> Result<RequestOptions, i32> {
+    let mut options = RequestOptions::default();
+    if options_json.is_null() {
+        return Ok(options);
+    }
+
+    let json_str = unsafe { CStr::from_ptr(options_json) }
+        .to_str()
+        .map_err(|_| {
+            set_last_error(ERR_INVALID_UTF8, "Options JSON string contains invalid UTF-8");
+            ERR_INVALID_UTF8
+        })?;
+
+    if json_str.trim().is_empty() {
+        return Ok(options);
+    }
+
+    let map: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(json_str).map_err(|e| {
+            set_last_error(ERR_INVALID_ARGUMENT, format!("Failed to parse options JSON: {}", e));
+            ERR_INVALID_ARGUMENT
+        })?;
+
+    let mut username = None;
+    let mut password = None;
+    for (key, value) in &map {
+        match key.as_str() {
+            "timeout_ms" => options.timeout_ms = as_i32(key, value)?,
+            "keep_alive" => {
+                options.keep_alive = as_i32(key, value)?;
+                if !(-1..=1).contains(&options.keep_alive) {
+                    set_last_error(
+                        ERR_INVALID_ARGUMENT,
+                        format!(
+                            "Option 'keep_alive' must be -1, 0 or 1, got {}",
+                            options.keep_alive
+                        ),
+                    );
+                    return Err(ERR_INVALID_ARGUMENT);
+                }
+            }
+            "deadline_unix_millis" => options.deadline_unix_millis = as_i64(key, value)?,
+            "force_chunked" => options.force_chunked = as_bool(key, value)?,
+            "username" => username = Some(as_string(key, value)?),
+            "password" => password = Some(as_string(key, value)?),
+            "bearer_token" => {
+                options.bearer_token = Some(as_string(key, value)?).filter(|t| !t.is_empty())
+            }
+            "max_response_bytes" => options.max_response_bytes = Some(as_u64(key, value)?),
+            // 0 is LabVIEW's "no token".
+            "cancel_token" => {
+                options.cancel_token = Some(as_u64(key, value)?).filter(|&t| t != 0)
+            }
+            _ => {
+                set_last_error(ERR_INVALID_ARGUMENT, format!("Unknown request option '{}'", key));
+                return Err(ERR_INVALID_ARGUMENT);
+            }
+        }
+    }
+    // An empty username means no authentication, password or not.
+    options.basic_auth = username.filter(|u| !u.is_empty()).map(|u| (u, password));
+    if options.basic_auth.is_some() && options.bearer_token.is_some() {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            "Options 'username' and 'bearer_token' cannot both be set",
+        );
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,163 @@
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::Url;
+
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT, ERR_OK};
+use crate::http::{self, HttpResponse};
+
+/// Result of following a paginated resource.
+pub struct Pages {
+    pub combined: HttpResponse,
+    /// True if a page after the first failed and collection stopped early.
+    pub partial: bool,
+}
+
+/// Find the target of the `rel="next"` entry in RFC 5988 Link headers.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    for value in headers.get_all(LINK) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        let mut rest = value;
+        while let Some(start) = rest.find('<') {
+            let after = &rest[start + 1..];
+            let Some(end) = after.find('>') else {
+                break;
+            };
+            let target = &after[..end];
+            let params = &after[end + 1..];
+            let params = &params[..params.find('<').unwrap_or(params.len())];
+
+            let is_next = params.split(';').any(|param| {
+                let Some((name, value)) = param.split_once('=') else {
+                    return false;
+                };
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            });
+            if is_next {
+                return Some(target.to_string());
+            }
+            rest = &after[end + 1..];
+        }
+    }
+    None
+}
+
+/// Merge page bodies: if every page is a JSON array the arrays are joined into
+/// one, otherwise the raw bytes are concatenated in page order.
+fn combine_bodies(bodies: Vec<Vec<u8>>) -> Vec<u8> {
+    let arrays: Option<Vec<Vec<serde_json::Value>>> = bodies
+        .iter()
+        .map(|b| serde_json::from_slice::<serde_json::Value>(b).ok()?.as_array().cloned())
+        .collect();
+
+    match arrays {
+        Some(arrays) => {
+            let merged: Vec<serde_json::Value> = arrays.into_iter().flatten().collect();
+            serde_json::to_vec(&merged).unwrap_or_default()
+        }
+        None => bodies.concat(),
+    }
+}
+
+/// GET `url` and follow `rel="next"` links, up to `max_pages` pages in total.
+///
+/// A failure on the first page is returned as an error. A transport error or
+/// non-2xx status on a later page stops collection and marks the result partial.
+/// The combined response carries the first page's status and headers.
+pub fn get_all_pages(
+    url: &str,
+    headers: HeaderMap,
+    max_pages: i32,
+    timeout_ms: i32,
+) -> Result<Pages, i32> {
+    if max_pages <= 0 {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("max_pages must be positive, got {}", max_pages),
+        );
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+
+    // The first page supplies the combined response's status and headers
+    let mut combined = http::get(url, headers.clone(), timeout_ms)?;
+    let mut current = Url::parse(url).ok();
+    let mut next = next_link(&combined.headers);
+    let mut bodies = vec![std::mem::take(&mut combined.body)];
+    let mut partial = false;
+
+    while (bodies.len() as i32) < max_pages {
+        let Some(target) = next.take() else {
+            break;
+        };
+        // Link targets may be relative to the page that returned them
+        let resolved = match current.as_ref().map(|base| base.join(&target)) {
+            Some(Ok(u)) => u,
+            _ => match Url::parse(&target) {
+                Ok(u) => u,
+                Err(e) => {
+                    set_last_error(ERR_OK, format!("Invalid next link '{}': {}", target, e));
+                    partial = true;
+                    break;
+                }
+            },
+        };
+
+        match http::get(resolved.as_str(), headers.clone(), timeout_ms) {
+            Ok(page) if (200..300).contains(&page.status) => {
+                next = next_link(&page.headers);
+                bodies.push(page.body);
+                current = Some(resolved);
+            }
+            Ok(page) => {
+                set_last_error(
+                    ERR_OK,
+                    format!(
+                        "Page {} returned status {}; stopping",
+                        bodies.len() + 1,
+                        page.status
+                    ),
+                );
+                partial = true;
+                break;
+            }
+            Err(_) => {
+                // The transport error message is already in the last-error slot
+                partial = true;
+                break;
+            }
+        }
+    }
+
+    combined.body = combine_bodies(bodies);
+    Ok(Pages { combined, partial })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, LINK};
+
+    #[test]
+    fn finds_next_among_several_links() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            HeaderValue::from_static("<https://x/p1>; rel=\"prev\", <https://x/p3>; rel=\"next\""),
+        );
+        assert_eq!(next_link(&headers).as_deref(), Some("https://x/p3"));
+        assert_eq!(next_link(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn merges_json_arrays_and_concatenates_the_rest() {
+        let arrays = vec![b"[1,2]".to_vec(), b"[3]".to_vec()];
+        assert_eq!(combine_bodies(arrays), b"[1,2,3]");
+        let text = vec![b"ab".to_vec(), b"cd".to_vec()];
+        assert_eq!(combine_bodies(text), b"abcd");
+    }
+}
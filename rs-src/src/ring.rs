@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use reqwest::blocking::Response;
+
+use crate::error::{
+    set_last_error, ERR_INVALID_ARGUMENT, ERR_INVALID_HANDLE, ERR_REQUEST_FAILED,
+    ERR_STREAM_CLOSED,
+};
+use crate::workers;
+
+/// Size of each read from the network.
+const READ_CHUNK: usize = 16 * 1024;
+
+struct RingState {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// Consumer called http_ring_close; the producer should stop.
+    closed: bool,
+    /// Producer reached the end of the body (or failed).
+    finished: bool,
+    error: Option<String>,
+}
+
+/// Bounded byte queue between the network thread and LabVIEW.
+struct Ring {
+    state: Mutex<RingState>,
+    not_full: Condvar,
+}
+
+static STREAMS: OnceLock<Mutex<HashMap<u64, Arc<Ring>>>> = OnceLock::new();
+
+// Starts at 1 so that 0 can serve as a sentinel "no handle" value in LabVIEW
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(1);
+
+fn streams() -> &'static Mutex<HashMap<u64, Arc<Ring>>> {
+    STREAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn lookup(handle: u64) -> Result<Arc<Ring>, i32> {
+    streams().lock().unwrap().get(&handle).cloned().ok_or_else(|| {
+        set_last_error(
+            ERR_INVALID_HANDLE,
+            format!("Invalid or already-closed stream handle: {}", handle),
+        );
+        ERR_INVALID_HANDLE
+    })
+}
+
+/// Producer loop: copy the body into the ring, blocking while it is full.
+fn produce(ring: Arc<Ring>, mut response: Response) {
+    let mut chunk = vec![0u8; READ_CHUNK];
+    loop {
+        let n = match response.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                let mut state = ring.state.lock().unwrap();
+                state.error = Some(format!("Stream read failed: {}", e));
+                state.finished = true;
+                return;
+            }
+        };
+
+        let mut pending = &chunk[..n];
+        let mut state = ring.state.lock().unwrap();
+        while !pending.is_empty() {
+            while state.data.len() == state.capacity && !state.closed {
+                state = ring.not_full.wait(state).unwrap();
+            }
+            if state.closed {
+                return;
+            }
+            let space = state.capacity - state.data.len();
+            let take = space.min(pending.len());
+            state.data.extend(&pending[..take]);
+            pending = &pending[take..];
+        }
+    }
+    ring.state.lock().unwrap().finished = true;
+}
+
+/// Start streaming `response`'s body into a new ring of `capacity` bytes.
+/// Returns the stream handle.
+pub fn start(response: Response, capacity: usize) -> Result<u64, i32> {
+    if capacity == 0 {
+        set_last_error(ERR_INVALID_ARGUMENT, "Ring capacity must be positive");
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+    let ring = Arc::new(Ring {
+        state: Mutex::new(RingState {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+            closed: false,
+            finished: false,
+            error: None,
+        }),
+        not_full: Condvar::new(),
+    });
+
+    let producer = ring.clone();
+    workers::spawn("ring", move || produce(producer, response)).map_err(|e| {
+        set_last_error(ERR_REQUEST_FAILED, format!("Failed to start stream thread: {}", e));
+        ERR_REQUEST_FAILED
+    })?;
+
+    let handle = NEXT_STREAM.fetch_add(1, Ordering::Relaxed);
+    streams().lock().unwrap().insert(handle, ring);
+    Ok(handle)
+}
+
+/// Copy whatever is buffered (up to `buf.len()` bytes) without blocking.
+///
+/// Returns Ok(n), possibly 0 while the stream is live. Once the body has ended
+/// and everything has been read, returns Err(ERR_STREAM_CLOSED); if the
+/// producer failed, the error is reported as ERR_REQUEST_FAILED instead.
+pub fn read(handle: u64, buf: &mut [u8]) -> Result<usize, i32> {
+    let ring = lookup(handle)?;
+    let mut state = ring.state.lock().unwrap();
+
+    if state.data.is_empty() && state.finished {
+        return Err(match &state.error {
+            Some(e) => {
+                set_last_error(ERR_REQUEST_FAILED, e.clone());
+                ERR_REQUEST_FAILED
+            }
+            None => {
+                set_last_error(ERR_STREAM_CLOSED, "Stream has ended");
+                ERR_STREAM_CLOSED
+            }
+        });
+    }
+    let n = buf.len().min(state.data.len());
+    for (dst, src) in buf.iter_mut().zip(state.data.drain(..n)) {
+        *dst = src;
+    }
+    drop(state);
+    ring.not_full.notify_one();
+    Ok(n)
+}
+
+/// Stop the stream and forget the handle. The producer thread exits at its
+/// next wake-up, dropping the connection.
+pub fn close(handle: u64) -> Result<(), i32> {
+    let ring = streams().lock().unwrap().remove(&handle).ok_or_else(|| {
+        set_last_error(
+            ERR_INVALID_HANDLE,
+            format!("Invalid or already-closed stream handle: {}", handle),
+        );
+        ERR_INVALID_HANDLE
+    })?;
+    ring.state.lock().unwrap().closed = true;
+    ring.not_full.notify_all();
+    Ok(())
+}
+
+/// Close every open stream, as http_ring_close would. The handles become
+/// invalid but their pointers must still be passed to http_ring_close.
+pub fn close_all() {
+    let rings: Vec<Arc<Ring>> = streams().lock().unwrap().drain().map(|(_, r)| r).collect();
+    for ring in rings {
+        ring.state.lock().unwrap().closed = true;
+        ring.not_full.notify_all();
+    }
+}
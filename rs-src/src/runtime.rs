@@ -1,32 +1,189 @@
-use once_cell::sync::OnceCell;
 use reqwest::blocking::Client;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 
+use crate::config::{self, ClientConfig};
+use crate::mirror;
 use crate::error::{set_last_error, ERR_CLIENT_INIT};
 
-static CLIENT: OnceCell<Client> = OnceCell::new();
-
-/// Returns a reference to the shared blocking HTTP client.
-/// The client is initialised on first call and reused for all subsequent calls.
-/// Reusing the client allows connection pooling across requests.
-pub fn get_client() -> Result<&'static Client, i32> {
-    CLIENT.get_or_try_init(|| {
-        Client::builder()
-            .use_rustls_tls()           // No OpenSSL dependency
-            .tcp_keepalive(Duration::from_secs(30))
-            .build()
-            .map_err(|e| {
-                set_last_error(format!("Failed to initialise HTTP client: {}", e));
-                ERR_CLIENT_INIT
-            })
+// The shared client. Requests hold their own Arc, so reset_client can replace
+// it while they finish on the old one.
+static CLIENT: RwLock<Option<Arc<Client>>> = RwLock::new(None);
+
+// Its async twin for cancellable requests; cloning it shares the pool.
+static ASYNC_CLIENT: RwLock<Option<reqwest::Client>> = RwLock::new(None);
+
+static CLIENT_CONFIG: OnceLock<Mutex<ClientConfig>> = OnceLock::new();
+
+fn client_config() -> &'static Mutex<ClientConfig> {
+    CLIENT_CONFIG.get_or_init(|| Mutex::new(ClientConfig::default()))
+}
+
+/// Modify the settings used to build the client.
+/// Returns Err(ERR_CLIENT_INIT) if the client has already been built, since the
+/// change could not take effect.
+pub fn configure_client(f: impl FnOnce(&mut ClientConfig)) -> Result<(), i32> {
+    if client_built() {
+        set_last_error(
+            ERR_CLIENT_INIT,
+            "HTTP client already initialised; apply client settings before the first request \
+             or after http_reset_client",
+        );
+        return Err(ERR_CLIENT_INIT);
+    }
+    f(&mut client_config().lock().unwrap());
+    Ok(())
+}
+
+/// The addresses `host` is pinned to by the dns_overrides setting, if any.
+pub fn dns_override(host: &str) -> Option<Vec<std::net::IpAddr>> {
+    let config = client_config().lock().unwrap();
+    let (_, ips) = config.dns_overrides.iter().find(|(h, _)| h.eq_ignore_ascii_case(host))?;
+    Some(ips.clone())
+}
+
+/// Whether either shared client currently exists.
+fn client_built() -> bool {
+    CLIENT.read().unwrap().is_some() || ASYNC_CLIENT.read().unwrap().is_some()
+}
+
+/// Returns the shared blocking HTTP client.
+/// The client is built on first use and reused for all subsequent calls, so
+/// connections are pooled across requests, until reset_client drops it.
+pub fn get_client() -> Result<Arc<Client>, i32> {
+    if let Some(client) = CLIENT.read().unwrap().as_ref() {
+        return Ok(client.clone());
+    }
+    let mut slot = CLIENT.write().unwrap();
+    // Another thread may have built it while we waited for the lock.
+    if let Some(client) = slot.as_ref() {
+        return Ok(client.clone());
+    }
+    let builder = reqwest::blocking::ClientBuilder::from(configured_builder()?);
+    let client = Arc::new(builder.build().map_err(init_failed)?);
+    *slot = Some(client.clone());
+    Ok(client)
+}
+
+/// Returns the shared async client, for requests that can be cancelled (see
+/// tasks). It is built from the same settings as the blocking client and
+/// reset with it, but keeps its own connection pool.
+pub fn get_async_client() -> Result<reqwest::Client, i32> {
+    if let Some(client) = ASYNC_CLIENT.read().unwrap().as_ref() {
+        return Ok(client.clone());
+    }
+    let mut slot = ASYNC_CLIENT.write().unwrap();
+    if let Some(client) = slot.as_ref() {
+        return Ok(client.clone());
+    }
+    let client = configured_builder()?.build().map_err(init_failed)?;
+    *slot = Some(client.clone());
+    Ok(client)
+}
+
+fn configured_builder() -> Result<reqwest::ClientBuilder, i32> {
+    let builder = reqwest::Client::builder()
+        .use_rustls_tls();          // No OpenSSL dependency
+    client_config().lock().unwrap().apply(builder)
+}
+
+fn init_failed(e: reqwest::Error) -> i32 {
+    set_last_error(ERR_CLIENT_INIT, format!("Failed to initialise HTTP client: {}", e));
+    ERR_CLIENT_INIT
+}
+
+/// A proxy URL with any password masked.
+fn redact_proxy(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("redacted"));
+            url.into()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// "system" when reqwest picks proxies from the environment, "none" when
+/// that is off and none are set, otherwise the configured proxies.
+fn proxy_json(config: &ClientConfig) -> serde_json::Value {
+    match (&config.http_proxy, &config.https_proxy) {
+        (None, None) if config.no_proxy_from_env => "none".into(),
+        (None, None) => "system".into(),
+        (http, https) => serde_json::json!({
+            "http": http.as_deref().map(redact_proxy),
+            "https": https.as_deref().map(redact_proxy),
+            "no_proxy": config.no_proxy,
+        }),
+    }
+}
+
+/// Snapshot of the configuration the shared client is (or will be) built with,
+/// including reqwest defaults we never override, plus the request-level
+/// settings. Once the client exists its settings can no longer change, so the
+/// "client" section always matches the live client.
+pub fn effective_config() -> serde_json::Value {
+    let config = client_config().lock().unwrap().clone();
+    // reqwest sends Accept: */* unless a default or the request overrides it.
+    let mut default_headers = serde_json::Map::new();
+    default_headers.insert("accept".into(), "*/*".into());
+    for (name, value) in &config.default_headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        default_headers.insert(name.as_str().into(), value.into());
+    }
+    let client = serde_json::json!({
+        "tls_backend": "rustls",
+        "cert_verification": config.cert_verification.name(),
+        "tls_min_version": config.tls_min_version.map_or("1.2", |v| v.name()),
+        "tls_max_version": config.tls_max_version.map_or("1.3", |v| v.name()),
+        "ca_cert_path": config.ca_cert_path.as_ref().map(|p| p.display().to_string()),
+        "client_cert_path": config.client_identity.as_ref().map(|i| i.0.display().to_string()),
+        "auto_referer": config.auto_referer,
+        "tcp_keepalive_ms": match config.disable_keepalive {
+            true => 0,
+            false => config.tcp_keepalive_ms,
+        },
+        "user_agent": config.user_agent,
+        "default_headers": default_headers,
+        "pool_idle_timeout_ms": match config.pool_idle_timeout_ms {
+            Some(0) => serde_json::Value::Null,
+            Some(ms) => ms.into(),
+            None => 90_000.into(),
+        },
+        "pool_max_idle_per_host": match config.disable_keepalive {
+            true => Some(0),
+            false => config.pool_max_idle_per_host,
+        },
+        "disable_keepalive": config.disable_keepalive,
+        "tcp_nodelay": config.tcp_nodelay,
+        "http_version": if config.http1_only { "http1" } else { "auto" },
+        // reqwest::blocking defaults
+        "timeout_ms": 30_000,
+        "connect_timeout_ms": null,
+        "proxy": proxy_json(&config),
+        "dns_overrides": config
+            .dns_overrides
+            .iter()
+            .map(|(host, ips)| (host.clone(), ips.iter().map(|ip| ip.to_string()).collect()))
+            .collect::<serde_json::Map<String, serde_json::Value>>(),
+        // No compression features are compiled in, so bodies arrive as sent.
+        "decompression": [],
+        "redirect": { "policy": "limited", "max": 10 },
+    });
+    serde_json::json!({
+        "client_built": client_built(),
+        "client": client,
+        "request": config::request_settings_json(),
+        "mirror": mirror::to_json(),
     })
 }
 
-/// Attempt to reinitialise the client. Only succeeds if the client has not
-/// yet been initialised (i.e. after http_shutdown clears it).
-/// In practice, shutdown drops the static - see store.rs for shutdown logic.
+/// Drop the shared clients so the next request builds new ones from the
+/// current client settings, which can be changed again until then. Requests
+/// already running keep the old client until they finish; its pooled
+/// connections close when the last of them is done.
 pub fn reset_client() {
-    // OnceCell doesn't support reset directly; the process must reinitialise.
-    // This is intentional - the client is tied to the process lifetime.
-    // Documented here for clarity.
+    let old = CLIENT.write().unwrap().take();
+    // Release the lock before the (possibly last) reference is dropped.
+    drop(old);
+    let old = ASYNC_CLIENT.write().unwrap().take();
+    drop(old);
 }
@@ -0,0 +1,211 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use ring::{digest, hmac};
+
+use crate::date::civil_from_days;
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT};
+
+/// Characters left unencoded: RFC 3986 unreserved (A-Z a-z 0-9 - . _ ~),
+/// which is also what AWS expects.
+pub const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Credentials and scope for one signature.
+pub struct SigningParams<'a> {
+    pub access_key: &'a str,
+    pub secret_key: &'a str,
+    pub region: &'a str,
+    pub service: &'a str,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+fn aws_encode(s: &str) -> String {
+    utf8_percent_encode(s, UNRESERVED).to_string()
+}
+
+/// Canonical URI: each path segment decoded then re-encoded with the AWS set
+/// (single encoding, as S3 expects).
+fn canonical_uri(url: &Url) -> String {
+    let path = url.path();
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(|seg| aws_encode(&percent_encoding::percent_decode_str(seg).decode_utf8_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Canonical query string: encoded pairs sorted by key, then value. Built from
+/// the raw query rather than form-decoded pairs, so a literal '+' is signed
+/// as %2B, not as a space.
+fn canonical_query(url: &Url) -> String {
+    let decode = |s: &str| aws_encode(&percent_encoding::percent_decode_str(s).decode_utf8_lossy());
+    let mut pairs: Vec<(String, String)> = url
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(k), decode(v))
+        })
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Host header value as the client will send it: port only when non-default.
+fn host_header(url: &Url) -> Result<String, i32> {
+    let host = url.host_str().ok_or_else(|| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("URL has no host: {}", url));
+        ERR_INVALID_ARGUMENT
+    })?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Compute a SigV4 signature and return the headers to send: the caller's
+/// headers plus `x-amz-date`, `Authorization` and, for S3, `x-amz-content-sha256`.
+/// Caller headers with those names are replaced, not sent twice.
+///
+/// `unix_secs` is the signing time; production callers pass the current time.
+pub fn sign(
+    params: &SigningParams,
+    method: &str,
+    url: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    unix_secs: i64,
+) -> Result<Vec<(String, String)>, i32> {
+    let url = Url::parse(url).map_err(|e| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid URL '{}': {}", url, e));
+        ERR_INVALID_ARGUMENT
+    })?;
+
+    let (year, month, day) = civil_from_days(unix_secs.div_euclid(86_400));
+    let secs_of_day = unix_secs.rem_euclid(86_400);
+    let date_stamp = format!("{:04}{:02}{:02}", year, month, day);
+    let amz_date = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date_stamp,
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    );
+    let payload_hash = sha256_hex(body);
+
+    let mut added = vec![("x-amz-date".to_string(), amz_date.clone())];
+    if params.service.eq_ignore_ascii_case("s3") {
+        added.push(("x-amz-content-sha256".to_string(), payload_hash.clone()));
+    }
+
+    // Canonical headers: lowercase names, trimmed values with inner whitespace
+    // collapsed, duplicates joined by commas, sorted by name.
+    let mut canonical: Vec<(String, String)> = Vec::new();
+    let replaced = |name: &str| {
+        name == "authorization" || added.iter().any(|(added, _)| added == name)
+    };
+    canonical.push(("host".to_string(), host_header(&url)?));
+    for name in headers.keys().filter(|name| !replaced(name.as_str())) {
+        let values: Vec<String> = headers
+            .get_all(name)
+            .iter()
+            .map(|v| {
+                String::from_utf8_lossy(v.as_bytes())
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        canonical.push((name.as_str().to_string(), values.join(",")));
+    }
+    canonical.extend(added.iter().cloned());
+    canonical.sort_by(|a, b| a.0.cmp(&b.0));
+    canonical.dedup_by(|a, b| a.0 == b.0);
+
+    let signed_headers = canonical
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = canonical
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method.to_ascii_uppercase(),
+        canonical_uri(&url),
+        canonical_query(&url),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let scope = format!(
+        "{}/{}/{}/aws4_request",
+        date_stamp, params.region, params.service
+    );
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let k_date = hmac_sha256(
+        format!("AWS4{}", params.secret_key).as_bytes(),
+        date_stamp.as_bytes(),
+    );
+    let k_region = hmac_sha256(&k_date, params.region.as_bytes());
+    let k_service = hmac_sha256(&k_region, params.service.as_bytes());
+    let k_signing = hmac_sha256(&k_service, b"aws4_request");
+    let signature = hex(&hmac_sha256(&k_signing, string_to_sign.as_bytes()));
+
+    let mut out: Vec<(String, String)> = headers
+        .iter()
+        .filter(|(k, _)| !replaced(k.as_str()))
+        .map(|(k, v)| {
+            (
+                k.as_str().to_string(),
+                String::from_utf8_lossy(v.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    out.extend(added);
+    out.push((
+        "Authorization".to_string(),
+        format!(
+            "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            ALGORITHM, params.access_key, scope, signed_headers, signature
+        ),
+    ));
+    Ok(out)
+}
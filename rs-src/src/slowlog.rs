@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use crate::buffer::write_bytes;
+use crate::config::slow_request_threshold_ms;
+
+/// Maximum entries kept; the oldest are dropped first.
+const MAX_ENTRIES: usize = 100;
+
+struct SlowRequest {
+    method: String,
+    url: String,
+    elapsed_ms: u64,
+}
+
+static SLOW_LOG: OnceLock<Mutex<VecDeque<SlowRequest>>> = OnceLock::new();
+
+fn slow_log() -> &'static Mutex<VecDeque<SlowRequest>> {
+    SLOW_LOG.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Record a finished request if it took longer than the configured threshold.
+pub fn record(method: &str, url: &str, elapsed_ms: u64) {
+    let threshold = slow_request_threshold_ms();
+    if threshold == 0 || elapsed_ms <= threshold {
+        return;
+    }
+    let mut log = slow_log().lock().unwrap();
+    if log.len() == MAX_ENTRIES {
+        log.pop_front();
+    }
+    log.push_back(SlowRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        elapsed_ms,
+    });
+}
+
+/// Copy the log into the buffer as a JSON array of {"method", "url",
+/// "elapsed_ms"} objects and clear it. The log is left intact if the buffer
+/// is too small.
+pub fn read_and_clear(buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    let mut log = slow_log().lock().unwrap();
+    let entries: Vec<serde_json::Value> = log
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "method": r.method,
+                "url": r.url,
+                "elapsed_ms": r.elapsed_ms,
+            })
+        })
+        .collect();
+    let json = serde_json::Value::Array(entries).to_string();
+    let written = write_bytes(json.as_bytes(), buf_ptr, buf_len);
+    if written >= 0 {
+        log.clear();
+    }
+    written
+}
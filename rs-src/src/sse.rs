@@ -0,0 +1,258 @@
+// Server-Sent Events: a background thread reads a text/event-stream response
+// and hands each event's data to a LabVIEW callback as it arrives. Each
+// stream's request runs with a cancel token of its own, so closing the
+// stream aborts it and wakes the reader even while the server is silent.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use reqwest::blocking::Response;
+
+use crate::error::{
+    set_last_error, ERR_INVALID_HANDLE, ERR_OK, ERR_REQUEST_FAILED, ERR_STREAM_CLOSED,
+};
+use crate::{cancel, workers};
+
+/// Receives one event's data (UTF-8, without a terminator) and its length.
+/// The bytes are only valid during the call.
+pub type SseCallback = extern "system" fn(*const u8, i32);
+
+/// Size of each read from the network.
+const READ_CHUNK: usize = 4 * 1024;
+
+#[derive(Default)]
+struct SseState {
+    /// Consumer called http_sse_close; the reader should stop.
+    closed: bool,
+    /// Reader reached the end of the stream (or failed).
+    finished: bool,
+    error: Option<String>,
+    last_event_id: String,
+}
+
+struct SseStream {
+    /// Aborts the stream's request, ending the reader's current read.
+    token: u64,
+    state: Mutex<SseState>,
+    /// Held while the callback runs, so close can wait for it to return.
+    callback: Mutex<()>,
+}
+
+/// One dispatched event.
+pub struct Event {
+    pub data: Vec<u8>,
+    /// The last event id seen when the event was dispatched.
+    pub id: String,
+}
+
+static STREAMS: OnceLock<Mutex<HashMap<u64, Arc<SseStream>>>> = OnceLock::new();
+
+// Starts at 1 so that 0 can serve as a sentinel "no handle" value in LabVIEW
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(1);
+
+fn streams() -> &'static Mutex<HashMap<u64, Arc<SseStream>>> {
+    STREAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Incremental event-stream parser. Feed it bytes as they arrive; it returns
+/// each event completed by a blank line.
+#[derive(Default)]
+pub struct Parser {
+    line: Vec<u8>,
+    /// The previous chunk ended in CR, so a leading LF is part of that break.
+    after_cr: bool,
+    started: bool,
+    data: Vec<u8>,
+    has_data: bool,
+    last_event_id: String,
+}
+
+impl Parser {
+    pub fn feed(&mut self, mut bytes: &[u8]) -> Vec<Event> {
+        if !self.started && !bytes.is_empty() {
+            self.started = true;
+            bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        }
+        let mut events = Vec::new();
+        for &b in bytes {
+            let after_cr = std::mem::replace(&mut self.after_cr, b == b'\r');
+            match b {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.process_line(&line));
+                }
+                _ => self.line.push(b),
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<Event> {
+        if line.is_empty() {
+            // Blank line: dispatch. An event with no data fields is dropped.
+            let mut data = std::mem::take(&mut self.data);
+            if !std::mem::take(&mut self.has_data) {
+                return None;
+            }
+            data.pop(); // the newline after the last data line
+            return Some(Event {
+                data,
+                id: self.last_event_id.clone(),
+            });
+        }
+        if line[0] == b':' {
+            return None; // comment
+        }
+        let (field, value) = match line.iter().position(|&b| b == b':') {
+            Some(i) => {
+                let value = &line[i + 1..];
+                (&line[..i], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &b""[..]),
+        };
+        match field {
+            b"data" => {
+                self.data.extend_from_slice(value);
+                self.data.push(b'\n');
+                self.has_data = true;
+            }
+            b"id" if !value.contains(&0) => {
+                self.last_event_id = String::from_utf8_lossy(value).into_owned();
+            }
+            // Event types and retry hints are not passed on; anything else is
+            // ignored, as the format requires.
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Reader loop: parse the body and call back with each event until the
+/// stream ends, fails, or is closed.
+fn run(stream: Arc<SseStream>, mut response: Response, callback: SseCallback) {
+    let mut parser = Parser::default();
+    let mut chunk = vec![0u8; READ_CHUNK];
+    let outcome = loop {
+        let n = match response.read(&mut chunk) {
+            Ok(0) => break None,
+            Ok(n) => n,
+            Err(e) => break Some(format!("Event stream read failed: {}", e)),
+        };
+        for event in parser.feed(&chunk[..n]) {
+            let _running = stream.callback.lock().unwrap();
+            {
+                let mut state = stream.state.lock().unwrap();
+                if state.closed {
+                    return;
+                }
+                state.last_event_id = event.id;
+            }
+            callback(event.data.as_ptr(), event.data.len() as i32);
+        }
+        if stream.state.lock().unwrap().closed {
+            return;
+        }
+    };
+    let mut state = stream.state.lock().unwrap();
+    state.error = outcome;
+    state.finished = true;
+}
+
+/// Make the request with `connect`, then call `callback` for each event in
+/// the response's body. Returns the stream handle.
+pub fn open(
+    connect: impl FnOnce() -> Result<Response, i32>,
+    callback: SseCallback,
+) -> Result<u64, i32> {
+    let token = cancel::new_token();
+    let started = cancel::scoped(token, connect).and_then(|response| {
+        let stream = Arc::new(SseStream {
+            token,
+            state: Mutex::default(),
+            callback: Mutex::default(),
+        });
+        let reader = stream.clone();
+        workers::spawn("sse", move || run(reader, response, callback)).map_err(|e| {
+            set_last_error(
+                ERR_REQUEST_FAILED,
+                format!("Failed to start event stream thread: {}", e),
+            );
+            ERR_REQUEST_FAILED
+        })?;
+        Ok(stream)
+    });
+    let stream = started.inspect_err(|_| {
+        let _ = cancel::free(token);
+    })?;
+    let handle = NEXT_STREAM.fetch_add(1, Ordering::Relaxed);
+    streams().lock().unwrap().insert(handle, stream);
+    Ok(handle)
+}
+
+/// Abort the stream's request, which ends the reader thread's read at once
+/// and closes the connection.
+fn abort(stream: &SseStream) {
+    let _ = cancel::cancel(stream.token);
+    let _ = cancel::free(stream.token);
+}
+
+/// The id of the last event received on the stream.
+pub fn last_event_id(handle: u64) -> Result<String, i32> {
+    let streams = streams().lock().unwrap();
+    match streams.get(&handle) {
+        Some(stream) => Ok(stream.state.lock().unwrap().last_event_id.clone()),
+        None => {
+            set_last_error(
+                ERR_INVALID_HANDLE,
+                format!("Invalid or already-closed event stream handle: {}", handle),
+            );
+            Err(ERR_INVALID_HANDLE)
+        }
+    }
+}
+
+/// Stop the stream and forget the handle. Waits for a callback in progress,
+/// and none starts after this returns; the request is aborted, so the reader
+/// thread exits straight away and the connection is closed. Reports how the
+/// stream had been doing: ERR_OK if it was still live, ERR_STREAM_CLOSED if
+/// the server had ended it, or ERR_REQUEST_FAILED if the connection broke.
+pub fn close(handle: u64) -> i32 {
+    let Some(stream) = streams().lock().unwrap().remove(&handle) else {
+        set_last_error(
+            ERR_INVALID_HANDLE,
+            format!("Invalid or already-closed event stream handle: {}", handle),
+        );
+        return ERR_INVALID_HANDLE;
+    };
+    stream.state.lock().unwrap().closed = true;
+    drop(stream.callback.lock().unwrap());
+    // Read the outcome before aborting, which would make it a failed read.
+    let outcome = {
+        let mut state = stream.state.lock().unwrap();
+        (state.finished, state.error.take())
+    };
+    abort(&stream);
+    match outcome {
+        (false, _) => ERR_OK,
+        (true, None) => {
+            set_last_error(ERR_STREAM_CLOSED, "Event stream was ended by the server");
+            ERR_STREAM_CLOSED
+        }
+        (true, Some(e)) => {
+            set_last_error(ERR_REQUEST_FAILED, e);
+            ERR_REQUEST_FAILED
+        }
+    }
+}
+
+/// Close every open stream, as http_sse_close would. The handles become
+/// invalid but their pointers must still be passed to http_sse_close.
+pub fn close_all() {
+    for (_, stream) in streams().lock().unwrap().drain() {
+        stream.state.lock().unwrap().closed = true;
+        abort(&stream);
+    }
+}
@@ -1,58 +1,129 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::{Mutex, OnceLock};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::error::{set_last_error, ERR_INVALID_HANDLE, ERR_NULL_PTR, ERR_BUFFER_TOO_SMALL};
+use crate::config;
+use crate::error::{
+    set_last_error, ERR_BUFFER_TOO_SMALL, ERR_INVALID_ARGUMENT, ERR_INVALID_HANDLE, ERR_NULL_PTR,
+};
+use crate::http::HttpResponse;
+use crate::trace;
 
 /// A stored HTTP response waiting to be read by the caller.
-pub struct StoredResponse {
-    pub body: Vec<u8>,
-    pub status: u32,
-}
+pub type StoredResponse = HttpResponse;
 
-static RESPONSES: OnceLock<Mutex<HashMap<u64, StoredResponse>>> = OnceLock::new();
+// Ordered by handle, which is also insertion order, so the oldest comes first.
+static RESPONSES: OnceLock<Mutex<BTreeMap<u64, StoredResponse>>> = OnceLock::new();
 
 // Starts at 1 so that 0 can serve as a sentinel "no handle" value in LabVIEW
 static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
 
-fn response_store() -> &'static Mutex<HashMap<u64, StoredResponse>> {
-    RESPONSES.get_or_init(|| Mutex::new(HashMap::new()))
+/// Responses evicted unread since the library was loaded.
+static EVICTED: AtomicU64 = AtomicU64::new(0);
+
+/// Most recently evicted handles remembered for error messages.
+const MAX_REMEMBERED_EVICTIONS: usize = 1024;
+
+// Oldest first; used to tell an evicted handle from a consumed one.
+static RECENTLY_EVICTED: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+
+fn response_store() -> &'static Mutex<BTreeMap<u64, StoredResponse>> {
+    RESPONSES.get_or_init(|| Mutex::new(BTreeMap::new()))
 }
 
-/// Insert a response into the store and return its handle.
-pub fn insert_response(body: Vec<u8>, status: u32) -> u64 {
+/// Insert a response into the store and return its handle. When the store
+/// already holds the configured maximum, the oldest responses are freed to
+/// make room; each eviction is counted, remembered so that a later use of the
+/// handle says what happened, and noted in the trace.
+pub fn insert_response(response: StoredResponse) -> u64 {
     let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
-    response_store()
-        .lock()
-        .unwrap()
-        .insert(handle, StoredResponse { body, status });
+    let limit = config::max_stored_responses();
+    let mut store = response_store().lock().unwrap();
+    while limit > 0 && store.len() >= limit {
+        let Some((evicted, _)) = store.pop_first() else { break };
+        EVICTED.fetch_add(1, Ordering::Relaxed);
+        let mut recent = RECENTLY_EVICTED.lock().unwrap();
+        if recent.len() == MAX_REMEMBERED_EVICTIONS {
+            recent.pop_front();
+        }
+        recent.push_back(evicted);
+        drop(recent);
+        trace::note(&format!(
+            "response store full ({} responses); evicted unread handle {}",
+            limit, evicted
+        ));
+    }
+    store.insert(handle, response);
     handle
 }
 
+/// Set the last error for a handle that is not in the store, saying whether
+/// it was evicted or, as `gone` puts it, already consumed or freed.
+fn missing_handle(handle: u64, gone: &str) -> i32 {
+    let message = if RECENTLY_EVICTED.lock().unwrap().contains(&handle) {
+        format!(
+            "Handle {} was evicted unread because the response store was full \
+             (see max_stored_responses)",
+            handle
+        )
+    } else {
+        format!("Invalid or {} handle: {}", gone, handle)
+    };
+    set_last_error(ERR_INVALID_HANDLE, message);
+    ERR_INVALID_HANDLE
+}
+
+/// Run `f` against a stored response without consuming it.
+/// Returns Err(ERR_INVALID_HANDLE) if the handle does not exist.
+pub fn with_response<R>(handle: u64, f: impl FnOnce(&StoredResponse) -> R) -> Result<R, i32> {
+    let store = response_store().lock().unwrap();
+    match store.get(&handle) {
+        Some(resp) => Ok(f(resp)),
+        None => Err(missing_handle(handle, "already-consumed")),
+    }
+}
+
+/// Whether `handle` is still stored, i.e. neither read nor freed.
+pub fn contains(handle: u64) -> bool {
+    response_store().lock().unwrap().contains_key(&handle)
+}
+
 /// Copy the response body into a caller-supplied buffer, then free the handle.
 /// Returns the number of bytes written, or a negative error code.
 /// The handle is consumed on success - it cannot be read twice.
+/// An empty body (e.g. a 304) needs no buffer, so buf_ptr may then be null.
 pub fn read_and_free_response(handle: u64, buf_ptr: *mut u8, buf_len: i32) -> i32 {
+    let mut store = response_store().lock().unwrap();
+    let Some(resp) = store.remove(&handle) else {
+        return missing_handle(handle, "already-consumed");
+    };
+    if resp.body.is_empty() {
+        return 0;
+    }
     if buf_ptr.is_null() {
-        set_last_error("Response buffer pointer is null");
+        store.insert(handle, resp);
+        set_last_error(ERR_NULL_PTR, "Response buffer pointer is null");
         return ERR_NULL_PTR;
     }
 
-    let mut store = response_store().lock().unwrap();
-    let Some(resp) = store.remove(&handle) else {
-        set_last_error(format!("Invalid or already-consumed handle: {}", handle));
-        return ERR_INVALID_HANDLE;
-    };
+    if buf_len < 0 {
+        store.insert(handle, resp);
+        set_last_error(ERR_INVALID_ARGUMENT, "Buffer length must not be negative");
+        return ERR_INVALID_ARGUMENT;
+    }
 
     let available = buf_len as usize;
     if resp.body.len() > available {
         // Put it back so the caller can retry with a larger buffer
         store.insert(handle, resp);
-        set_last_error(format!(
-            "Buffer too small: need {} bytes, got {}",
-            store[&handle].body.len(),
-            available
-        ));
+        set_last_error(
+            ERR_BUFFER_TOO_SMALL,
+            format!(
+                "Buffer too small: need {} bytes, got {}",
+                store[&handle].body.len(),
+                available
+            ),
+        );
         return ERR_BUFFER_TOO_SMALL;
     }
 
@@ -63,18 +134,87 @@ pub fn read_and_free_response(handle: u64, buf_ptr: *mut u8, buf_len: i32) -> i3
     copy_len as i32
 }
 
+/// Where one response's body landed in a batch read.
+pub struct BatchEntry {
+    pub offset: usize,
+    pub len: usize,
+    pub status: u32,
+}
+
+/// Copy the bodies of several responses back-to-back into `buf`, then free
+/// them. A handle of 0 is a placeholder: it gets an empty entry with status 0.
+///
+/// The layout is returned even when `buf` is too small (with
+/// ERR_BUFFER_TOO_SMALL), so the caller can see the total size needed; in
+/// that case, as for any error, nothing is freed.
+pub fn read_and_free_batch(
+    handles: &[u64],
+    buf: &mut [u8],
+) -> (Result<usize, i32>, Vec<BatchEntry>) {
+    let mut store = response_store().lock().unwrap();
+
+    let mut entries = Vec::with_capacity(handles.len());
+    let mut total = 0;
+    for &handle in handles {
+        let (len, status) = match handle {
+            0 => (0, 0),
+            _ => match store.get(&handle) {
+                Some(resp) => (resp.body.len(), resp.status),
+                None => return (Err(missing_handle(handle, "already-consumed")), Vec::new()),
+            },
+        };
+        entries.push(BatchEntry { offset: total, len, status });
+        total += len;
+    }
+
+    // The total is returned as an i32.
+    if total > i32::MAX as usize {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!("Batch of {} bytes is too large to read in one call", total),
+        );
+        return (Err(ERR_INVALID_ARGUMENT), Vec::new());
+    }
+    if total > buf.len() {
+        set_last_error(
+            ERR_BUFFER_TOO_SMALL,
+            format!("Buffer too small: need {} bytes, got {}", total, buf.len()),
+        );
+        return (Err(ERR_BUFFER_TOO_SMALL), entries);
+    }
+
+    for (&handle, entry) in handles.iter().zip(&entries) {
+        if let Some(resp) = store.get(&handle) {
+            buf[entry.offset..entry.offset + entry.len].copy_from_slice(&resp.body);
+        }
+    }
+    for handle in handles {
+        store.remove(handle);
+    }
+    (Ok(total), entries)
+}
+
 /// Free a response handle without reading it.
 /// Call this in error-handling paths where you received a handle but
 /// do not intend to read the response.
 pub fn free_response(handle: u64) -> i32 {
     let removed = response_store().lock().unwrap().remove(&handle);
     if removed.is_none() {
-        set_last_error(format!("Invalid or already-freed handle: {}", handle));
-        return ERR_INVALID_HANDLE;
+        return missing_handle(handle, "already-freed");
     }
     0
 }
 
+/// Remove a response if it is still stored, without treating a missing
+/// handle as an error. Returns true if an entry was removed.
+///
+/// Every read copies out of the store while holding the store lock, so no
+/// pointer into a stored body outlives a call; once this returns, no read of
+/// the handle can be in progress or start later.
+pub fn abort_response(handle: u64) -> bool {
+    response_store().lock().unwrap().remove(&handle).is_some()
+}
+
 /// Clear all stored responses. Called from http_shutdown.
 pub fn clear_all_responses() {
     response_store().lock().unwrap().clear();
@@ -85,3 +225,9 @@ pub fn clear_all_responses() {
 pub fn pending_response_count() -> usize {
     response_store().lock().unwrap().len()
 }
+
+/// Returns the number of responses evicted unread since the library was
+/// loaded.
+pub fn evicted_response_count() -> u64 {
+    EVICTED.load(Ordering::Relaxed)
+}
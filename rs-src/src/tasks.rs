@@ -0,0 +1,61 @@
+// A Tokio runtime for requests that must be abortable. The blocking client
+// cannot stop a request it is running, so cancellable requests run as tasks
+// on reqwest's async client instead: aborting a task drops its future, which
+// closes the connection at once.
+//
+// The runtime is single-threaded and driven by a tracked worker thread, so
+// http_shutdown_threads can stop it like any other. It starts on first use.
+
+use std::future::Future;
+use std::sync::Mutex;
+
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+
+use crate::error::{set_last_error, ERR_REQUEST_FAILED};
+use crate::workers;
+
+/// The running runtime and the sender that stops its thread.
+static RUNTIME: Mutex<Option<(Handle, oneshot::Sender<()>)>> = Mutex::new(None);
+
+fn start_failed(e: std::io::Error) -> i32 {
+    set_last_error(
+        ERR_REQUEST_FAILED,
+        format!("Failed to start request runtime: {}", e),
+    );
+    ERR_REQUEST_FAILED
+}
+
+fn handle() -> Result<Handle, i32> {
+    let mut slot = RUNTIME.lock().unwrap();
+    if let Some((handle, _)) = slot.as_ref() {
+        return Ok(handle.clone());
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(start_failed)?;
+    let handle = runtime.handle().clone();
+    let (stop, stopped) = oneshot::channel::<()>();
+    // Dropping the runtime when block_on returns aborts every task left.
+    workers::spawn("tasks", move || {
+        let _ = runtime.block_on(stopped);
+    })
+    .map_err(start_failed)?;
+    *slot = Some((handle.clone(), stop));
+    Ok(handle)
+}
+
+/// Run `task` on the runtime, starting it if need be.
+pub fn spawn(task: impl Future<Output = ()> + Send + 'static) -> Result<AbortHandle, i32> {
+    Ok(handle()?.spawn(task).abort_handle())
+}
+
+/// Stop the runtime, aborting its tasks; its thread then exits. Called from
+/// http_shutdown_threads. The next spawn starts a new one.
+pub fn stop() {
+    if let Some((_, stop)) = RUNTIME.lock().unwrap().take() {
+        let _ = stop.send(());
+    }
+}
@@ -0,0 +1,292 @@
+// In-process HTTP/1.1 server for the crate's own tests.
+//
+// Each test starts a server on an ephemeral localhost port with a handler that
+// maps the recorded request to a canned reply, so tests run offline and
+// deterministically. The server records every request it receives and counts
+// connections opened and closed, which lets tests assert on what actually went over the wire.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// A request as the server received it.
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: String,
+    /// Path including any query string.
+    pub target: String,
+    /// "HTTP/1.0" or "HTTP/1.1".
+    pub version: String,
+    /// Header names are lowercased; order is as received.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Recorded {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or("")
+    }
+}
+
+/// A canned response.
+#[derive(Clone, Debug)]
+pub struct Reply {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Duration,
+    chunked: bool,
+}
+
+impl Reply {
+    pub fn status(status: u16) -> Self {
+        Reply {
+            status,
+            reason: "Test".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            delay: Duration::ZERO,
+            chunked: false,
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Reply::status(200).body(body)
+    }
+
+    /// Send this reason phrase in the status line instead of "Test".
+    pub fn reason(mut self, reason: &str) -> Self {
+        self.reason = reason.to_string();
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Wait this long before sending anything.
+    pub fn delay_ms(mut self, ms: u64) -> Self {
+        self.delay = Duration::from_millis(ms);
+        self
+    }
+
+    /// Send the body with `Transfer-Encoding: chunked` in small chunks.
+    pub fn chunked(mut self) -> Self {
+        self.chunked = true;
+        self
+    }
+}
+
+type Handler = dyn Fn(&Recorded) -> Reply + Send + Sync;
+
+struct Shared {
+    handler: Box<Handler>,
+    requests: Mutex<Vec<Recorded>>,
+    connections: AtomicUsize,
+    closed: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+pub struct TestServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl TestServer {
+    /// Start a server that answers every request with `handler`.
+    pub fn start(handler: impl Fn(&Recorded) -> Reply + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Arc::new(Shared {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+            connections: AtomicUsize::new(0),
+            closed: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accept_shared = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_shared.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                accept_shared.connections.fetch_add(1, Ordering::SeqCst);
+                let conn_shared = accept_shared.clone();
+                thread::spawn(move || {
+                    serve(stream, &conn_shared);
+                    conn_shared.closed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        TestServer { addr, shared }
+    }
+
+    /// Start a server that always sends the same reply.
+    pub fn reply(reply: Reply) -> Self {
+        TestServer::start(move |_| reply.clone())
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.shared.requests.lock().unwrap().clone()
+    }
+
+    /// The only request received; panics if there were none or several.
+    pub fn request(&self) -> Recorded {
+        let requests = self.requests();
+        assert_eq!(requests.len(), 1, "expected exactly one request");
+        requests.into_iter().next().unwrap()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    /// Connections that have ended, closed by either side. The server only
+    /// notices a client closing once it next reads, after any reply delay.
+    pub fn closed(&self) -> usize {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag and exits.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Serialise tests that touch process-wide settings (or make requests that
+/// those settings would affect). Poisoning from a failed test is ignored.
+pub fn serial() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn serve(stream: TcpStream, shared: &Shared) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_request(&mut reader) {
+        if shared.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        shared.requests.lock().unwrap().push(request.clone());
+        let reply = (shared.handler)(&request);
+        thread::sleep(reply.delay);
+        if write_reply(&mut writer, &reply, request.method == "HEAD").is_err() {
+            return;
+        }
+        let close = request.version == "HTTP/1.0"
+            || request
+                .header("connection")
+                .is_some_and(|v| v.eq_ignore_ascii_case("close"));
+        if close {
+            return;
+        }
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<Recorded> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+    let version = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() {
+            break;
+        }
+        let (name, value) = trimmed.split_once(':')?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = Recorded {
+        method,
+        target,
+        version,
+        headers,
+        body: Vec::new(),
+    };
+    if request
+        .header("transfer-encoding")
+        .is_some_and(|v| v.contains("chunked"))
+    {
+        loop {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            let size = usize::from_str_radix(line.trim(), 16).ok()?;
+            let mut chunk = vec![0u8; size + 2];
+            reader.read_exact(&mut chunk).ok()?;
+            if size == 0 {
+                break;
+            }
+            request.body.extend_from_slice(&chunk[..size]);
+        }
+    } else if let Some(len) = request.header("content-length") {
+        let mut body = vec![0u8; len.parse().ok()?];
+        reader.read_exact(&mut body).ok()?;
+        request.body = body;
+    }
+    Some(request)
+}
+
+/// Replies to HEAD keep their headers (including Content-Length) but send no body.
+fn write_reply(writer: &mut impl Write, reply: &Reply, head_request: bool) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", reply.status, reply.reason);
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if reply.chunked {
+        head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+        writer.write_all(head.as_bytes())?;
+        if head_request {
+            return writer.flush();
+        }
+        for chunk in reply.body.chunks(7) {
+            writer.write_all(format!("{:x}\r\n", chunk.len()).as_bytes())?;
+            writer.write_all(chunk)?;
+            writer.write_all(b"\r\n")?;
+            writer.flush()?;
+        }
+        writer.write_all(b"0\r\n\r\n")?;
+    } else {
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", reply.body.len()));
+        writer.write_all(head.as_bytes())?;
+        if !head_request {
+            writer.write_all(&reply.body)?;
+        }
+    }
+    writer.flush()
+}
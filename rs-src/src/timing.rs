@@ -0,0 +1,112 @@
+// Connection setup timings. A connector layer times every connection the
+// client opens, together with the DNS lookup made for it, and files the
+// result under the connection's local address. Responses carry that address
+// in their HttpInfo, so each request claims the timings of exactly the
+// connection it was sent on, whatever else is in flight to the same host.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper_util::client::legacy::connect::{Connection, HttpInfo};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Most recent connections kept for responses to claim; older ones are dropped.
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long opening one connection took.
+#[derive(Clone, Copy)]
+pub struct ConnectTimes {
+    /// The DNS lookup, if one was made.
+    pub dns: Option<Duration>,
+    /// The TCP connect and, for https, the TLS handshake.
+    pub connect: Duration,
+}
+
+static CONNECTIONS: Mutex<VecDeque<(SocketAddr, ConnectTimes)>> = Mutex::new(VecDeque::new());
+
+/// Where the resolver stores its lookup time for the connection being opened.
+pub type LookupSlot = Arc<Mutex<Option<Duration>>>;
+
+tokio::task_local! {
+    static LOOKUP: LookupSlot;
+}
+
+/// The slot of the connection the caller is opening, or None outside one.
+pub fn lookup_slot() -> Option<LookupSlot> {
+    LOOKUP.try_with(Arc::clone).ok()
+}
+
+/// Claim the timings of the connection `response` arrived on. None when the
+/// request reused a pooled connection, whose setup was already claimed.
+pub fn take(response: &reqwest::blocking::Response) -> Option<ConnectTimes> {
+    let local = response.extensions().get::<HttpInfo>()?.local_addr();
+    let mut connections = CONNECTIONS.lock().unwrap();
+    let index = connections.iter().position(|(addr, _)| *addr == local)?;
+    connections.remove(index).map(|(_, times)| times)
+}
+
+fn record(local: SocketAddr, times: ConnectTimes) {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    // A port reused since an unclaimed connection replaces its entry.
+    connections.retain(|(addr, _)| *addr != local);
+    if connections.len() == MAX_CONNECTIONS {
+        connections.pop_front();
+    }
+    connections.push_back((local, times));
+}
+
+/// Connector layer that times each connection; added to both clients.
+#[derive(Clone)]
+pub struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = Timed<S>;
+
+    fn layer(&self, inner: S) -> Timed<S> {
+        Timed(inner)
+    }
+}
+
+#[derive(Clone)]
+pub struct Timed<S>(S);
+
+impl<S, R> Service<R> for Timed<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Response: Connection + Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, target: R) -> Self::Future {
+        let lookup = LookupSlot::default();
+        // The resolver runs while the connector future is polled, in scope.
+        let connecting = LOOKUP.scope(lookup.clone(), self.0.call(target));
+        let started = Instant::now();
+        Box::pin(async move {
+            let conn = connecting.await?;
+            let total = started.elapsed();
+            let dns = *lookup.lock().unwrap();
+            let mut extras = http::Extensions::new();
+            conn.connected().get_extras(&mut extras);
+            if let Some(info) = extras.get::<HttpInfo>() {
+                let connect = total.saturating_sub(dns.unwrap_or_default());
+                record(info.local_addr(), ConnectTimes { dns, connect });
+            }
+            Ok(conn)
+        })
+    }
+}
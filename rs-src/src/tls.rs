@@ -0,0 +1,189 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::verify_server_name;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::sign::CertifiedKey;
+use rustls::{DigitallySignedStruct, SignatureScheme};
+
+use crate::config::TlsVersion;
+
+/// Verifier that checks the certificate is issued for the requested hostname but
+/// does not validate the chain to a trusted root. REDUCED SECURITY: anyone can
+/// mint a self-signed certificate for any name.
+///
+/// Handshake signatures are still verified, so the server must hold the key.
+#[derive(Debug)]
+struct HostnameOnlyVerifier {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for HostnameOnlyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let cert = ParsedCertificate::try_from(end_entity)?;
+        verify_server_name(&cert, server_name)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// A client certificate chain and its private key, for mutual TLS.
+pub type ClientKey = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// Build a rustls config that only checks the hostname (see HostnameOnlyVerifier),
+/// offering TLS versions `min` to `max` and presenting `client_key` if the
+/// server asks for a certificate. Passed to reqwest via
+/// `use_preconfigured_tls`, which bypasses the builder's identity, so the
+/// client certificate has to go in here.
+pub fn hostname_only_tls_config(
+    min: TlsVersion,
+    max: TlsVersion,
+    client_key: Option<ClientKey>,
+) -> Result<rustls::ClientConfig, rustls::Error> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let algorithms = provider.signature_verification_algorithms;
+    let versions: Vec<_> = [
+        (TlsVersion::Tls13, &rustls::version::TLS13),
+        (TlsVersion::Tls12, &rustls::version::TLS12),
+    ]
+    .into_iter()
+    .filter(|(v, _)| (min..=max).contains(v))
+    .map(|(_, version)| version)
+    .collect();
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&versions)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(HostnameOnlyVerifier { algorithms }));
+    let mut config = match client_key {
+        Some((certs, key)) => builder.with_client_auth_cert(certs, key)?,
+        None => builder.with_no_client_auth(),
+    };
+    // reqwest is built without HTTP/2, so only offer HTTP/1.1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// Read the certificates of a PEM file, one or a bundle, to trust as roots.
+/// Each BEGIN/END CERTIFICATE block is decoded and parsed; text between
+/// blocks (such as the comments in OS bundles) is ignored. The error names
+/// the file and, for a bad certificate, its position in the bundle.
+pub fn load_root_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read CA certificate file '{}': {}", path.display(), e))?;
+
+    let mut certs = Vec::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let index = certs.len() + 1;
+        let end = rest[start..].find(PEM_END).ok_or_else(|| {
+            format!("Certificate {} in '{}' has no END line", index, path.display())
+        })? + start + PEM_END.len();
+        let block = &rest[start..end];
+        rest = &rest[end..];
+
+        let der = CertificateDer::from_pem_slice(block.as_bytes()).map_err(|e| {
+            format!("Certificate {} in '{}' is malformed: {}", index, path.display(), e)
+        })?;
+        rustls::RootCertStore::empty().add(der.clone()).map_err(|e| {
+            format!("Certificate {} in '{}' is not usable: {}", index, path.display(), e)
+        })?;
+        let cert = reqwest::Certificate::from_der(&der).map_err(|e| e.to_string())?;
+        certs.push(cert);
+    }
+    if certs.is_empty() {
+        return Err(format!("No certificates found in '{}'", path.display()));
+    }
+    Ok(certs)
+}
+
+fn read_pem_file(path: &Path, what: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} file '{}' not found", what, path.display()),
+        _ => format!("Cannot read {} file '{}': {}", what.to_lowercase(), path.display(), e),
+    })
+}
+
+/// Load a client certificate chain and its private key, both PEM, as the
+/// identity for mutual TLS. The key must be unencrypted PKCS#8, PKCS#1 (RSA)
+/// or SEC1 (EC) and must belong to the first certificate. Errors say whether
+/// a file is missing or its contents are wrong.
+pub fn load_identity(cert_path: &Path, key_path: &Path) -> Result<reqwest::Identity, String> {
+    let (mut pem, key_pem, _) = read_identity(cert_path, key_path)?;
+    pem.push(b'\n');
+    pem.extend_from_slice(&key_pem);
+    reqwest::Identity::from_pem(&pem).map_err(|e| format!("Invalid client identity: {}", e))
+}
+
+/// load_identity, giving the parsed chain and key for a rustls config.
+pub fn load_client_key(cert_path: &Path, key_path: &Path) -> Result<ClientKey, String> {
+    read_identity(cert_path, key_path).map(|(_, _, client_key)| client_key)
+}
+
+/// Read and check both files of a client identity: their PEM, then the
+/// parsed chain and key.
+fn read_identity(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<(Vec<u8>, Vec<u8>, ClientKey), String> {
+    let cert_pem = read_pem_file(cert_path, "Client certificate")?;
+    let key_pem = read_pem_file(key_path, "Client key")?;
+
+    let certs = CertificateDer::pem_slice_iter(&cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Bad certificate in '{}': {}", cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in '{}'", cert_path.display()));
+    }
+    let key = PrivateKeyDer::from_pem_slice(&key_pem).map_err(|e| {
+        format!(
+            "No usable private key in '{}' (unencrypted PKCS#8, PKCS#1 or SEC1 PEM expected): {}",
+            key_path.display(),
+            e
+        )
+    })?;
+    let provider = rustls::crypto::ring::default_provider();
+    CertifiedKey::from_der(certs.clone(), key.clone_key(), &provider).map_err(|e| {
+        format!(
+            "Client key '{}' does not fit certificate '{}': {}",
+            key_path.display(),
+            cert_path.display(),
+            e
+        )
+    })?;
+    Ok((cert_pem, key_pem, (certs, key)))
+}
@@ -0,0 +1,142 @@
+// Per-request tracing for deep debugging.
+//
+// While tracing is on, each request made on a thread replaces that thread's
+// trace with a curl-style transcript: the DNS answer for the host, the request
+// line, headers and body, any connect retries, the response status line and
+// headers, the peer address and timings. It is kept until the next request
+// on the same thread, so it can be read right after the call that misbehaved.
+
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::time::Instant;
+
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{HeaderMap, HeaderName};
+
+use crate::dns;
+use crate::error::last_error;
+
+/// Headers whose values never appear in a trace.
+const REDACTED: [&str; 5] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// How much of a request body is shown.
+const BODY_PREVIEW: usize = 256;
+
+struct Trace {
+    started: Instant,
+    text: String,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+fn with_trace(f: impl FnOnce(&mut Trace)) {
+    TRACE.with(|t| {
+        if let Some(trace) = t.borrow_mut().as_mut() {
+            f(trace);
+        }
+    });
+}
+
+/// Append an event line stamped with the time since the trace started.
+pub fn note(event: &str) {
+    with_trace(|t| {
+        let elapsed_ms = t.started.elapsed().as_millis();
+        let _ = writeln!(t.text, "* {:>5} ms  {}", elapsed_ms, event);
+    });
+}
+
+fn write_headers(text: &mut String, prefix: char, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = if is_redacted(name) || value.is_sensitive() {
+            "[redacted]".into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        let _ = writeln!(text, "{} {}: {}", prefix, name, value);
+    }
+}
+
+fn is_redacted(name: &HeaderName) -> bool {
+    REDACTED.contains(&name.as_str())
+}
+
+/// Start a fresh trace for `request`, discarding the previous one.
+pub fn begin(request: &Request) {
+    TRACE.with(|t| {
+        *t.borrow_mut() = Some(Trace {
+            started: Instant::now(),
+            text: String::new(),
+        })
+    });
+
+    let url = request.url();
+    if let Some(host) = url.domain() {
+        match dns::resolve(host) {
+            Ok(ips) => {
+                let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+                note(&format!("resolve {} -> {}", host, ips.join(", ")));
+            }
+            Err(_) => note(&format!("resolve {} failed: {}", host, last_error())),
+        }
+    }
+
+    with_trace(|t| {
+        let query = url.query().map(|q| format!("?{}", q)).unwrap_or_default();
+        let _ = writeln!(
+            t.text,
+            "> {} {}{} {:?}",
+            request.method(),
+            url.path(),
+            query,
+            request.version()
+        );
+        let host = url.host_str().unwrap_or_default();
+        match url.port() {
+            Some(port) => writeln!(t.text, "> host: {}:{}", host, port),
+            None => writeln!(t.text, "> host: {}", host),
+        }
+        .ok();
+        write_headers(&mut t.text, '>', request.headers());
+        match request.body().map(|b| b.as_bytes()) {
+            None => {}
+            Some(None) => t.text.push_str("> [streaming body]\n"),
+            Some(Some(body)) => {
+                let shown = body.len().min(BODY_PREVIEW);
+                let preview = body[..shown].escape_ascii();
+                let _ = write!(t.text, "> [{}-byte body] {}", body.len(), preview);
+                t.text.push_str(if shown < body.len() { "...\n" } else { "\n" });
+            }
+        }
+    });
+}
+
+/// Drop the current thread's trace.
+pub fn clear() {
+    TRACE.with(|t| *t.borrow_mut() = None);
+}
+
+/// Record the response head once it has arrived.
+pub fn response(response: &Response, alpn: &str) {
+    let peer = response
+        .remote_addr()
+        .map_or_else(|| "unknown address".to_string(), |a| a.to_string());
+    let protocol = if alpn.is_empty() { String::new() } else { format!(", ALPN {}", alpn) };
+    note(&format!("headers received from {}{}", peer, protocol));
+    with_trace(|t| {
+        let _ = writeln!(t.text, "< {:?} {}", response.version(), response.status());
+        write_headers(&mut t.text, '<', response.headers());
+    });
+}
+
+/// The current thread's trace, or an empty string if nothing was traced.
+pub fn text() -> String {
+    TRACE.with(|t| t.borrow().as_ref().map(|t| t.text.clone()).unwrap_or_default())
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use reqwest::blocking::Body;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_RANGE};
+use reqwest::Url;
+
+use crate::config;
+use crate::error::{set_last_error, ERR_INVALID_ARGUMENT, ERR_IO};
+use crate::http::{self, HttpResponse};
+use crate::options::RequestOptions;
+use crate::runtime::get_client;
+
+/// PUT the whole file at `path` as the request body, streamed from disk with
+/// Content-Length taken from the file size.
+pub fn put_file(
+    url: &str,
+    headers: HeaderMap,
+    path: &Path,
+    timeout_ms: i32,
+) -> Result<HttpResponse, i32> {
+    let io_error = |what: &str, e: std::io::Error| {
+        set_last_error(ERR_IO, format!("Failed to {} '{}': {}", what, path.display(), e));
+        ERR_IO
+    };
+
+    let file = File::open(path).map_err(|e| io_error("open", e))?;
+    let metadata = file.metadata().map_err(|e| io_error("read metadata of", e))?;
+    if !metadata.is_file() {
+        set_last_error(ERR_IO, format!("'{}' is not a regular file", path.display()));
+        return Err(ERR_IO);
+    }
+    config::check_request_size(metadata.len())?;
+
+    // Body::from(File) reads the length from the metadata, so the request
+    // carries Content-Length rather than chunked encoding.
+    let builder = get_client()?.put(url).body(Body::from(file));
+    http::execute(builder, headers, &RequestOptions::with_timeout(timeout_ms))
+}
+
+/// PUT bytes `offset..offset + length` of the file at `path` as one part of a
+/// resumable or multipart upload. The slice is streamed from disk, never
+/// loaded whole.
+///
+/// Adds `Content-Range: bytes first-last/total` unless the caller set one, and
+/// for `part_number` > 0 a `partNumber` query parameter (S3 multipart style)
+/// unless the URL already has one.
+pub fn upload_part(
+    url: &str,
+    mut headers: HeaderMap,
+    path: &Path,
+    offset: u64,
+    length: u64,
+    part_number: i32,
+    timeout_ms: i32,
+) -> Result<HttpResponse, i32> {
+    let io_error = |what: &str, e: std::io::Error| {
+        set_last_error(ERR_IO, format!("Failed to {} '{}': {}", what, path.display(), e));
+        ERR_IO
+    };
+
+    let mut file = File::open(path).map_err(|e| io_error("open", e))?;
+    let total = file.metadata().map_err(|e| io_error("read metadata of", e))?.len();
+    if length == 0 || offset.checked_add(length).is_none_or(|end| end > total) {
+        set_last_error(
+            ERR_INVALID_ARGUMENT,
+            format!(
+                "Part {}+{} is outside the {} byte file '{}'",
+                offset,
+                length,
+                total,
+                path.display()
+            ),
+        );
+        return Err(ERR_INVALID_ARGUMENT);
+    }
+    config::check_request_size(length)?;
+    file.seek(SeekFrom::Start(offset)).map_err(|e| io_error("seek in", e))?;
+
+    let mut url = Url::parse(url).map_err(|e| {
+        set_last_error(ERR_INVALID_ARGUMENT, format!("Invalid URL '{}': {}", url, e));
+        ERR_INVALID_ARGUMENT
+    })?;
+    if part_number > 0 && !url.query_pairs().any(|(k, _)| k == "partNumber") {
+        url.query_pairs_mut().append_pair("partNumber", &part_number.to_string());
+    }
+
+    if !headers.contains_key(CONTENT_RANGE) {
+        let range = format!("bytes {}-{}/{}", offset, offset + length - 1, total);
+        // Digits, spaces and punctuation only, so always a valid header value.
+        headers.insert(CONTENT_RANGE, HeaderValue::from_str(&range).unwrap());
+    }
+
+    let body = Body::sized(file.take(length), length);
+    let builder = get_client()?.put(url).body(body);
+    http::execute(builder, headers, &RequestOptions::with_timeout(timeout_ms))
+}
@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Background threads started by the library (mirroring, streaming, async
+// requests). Tracked so they can be joined before the library is unloaded.
+static WORKERS: OnceLock<Mutex<Vec<JoinHandle<()>>>> = OnceLock::new();
+
+// Set while shutdown() is waiting, so no new threads start behind its back.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// How often shutdown() checks whether the remaining threads have finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn workers() -> &'static Mutex<Vec<JoinHandle<()>>> {
+    WORKERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Spawn a tracked background thread. Finished threads are pruned as new
+/// ones are added so the list does not grow without bound.
+pub fn spawn(name: &str, f: impl FnOnce() + Send + 'static) -> std::io::Result<()> {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return Err(std::io::Error::other("library is shutting down"));
+    }
+    let handle = std::thread::Builder::new()
+        .name(format!("http-rs-{}", name))
+        .spawn(f)?;
+    let mut workers = workers().lock().unwrap();
+    workers.retain(|h| !h.is_finished());
+    workers.push(handle);
+    Ok(())
+}
+
+/// Join every tracked thread, waiting at most `timeout` for them to finish.
+/// Threads must already have been asked to stop. Refuses new threads while
+/// waiting. Returns how many threads were still running at the deadline;
+/// those stay tracked so a later call can join them.
+pub fn shutdown(timeout: Duration) -> usize {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    let deadline = Instant::now() + timeout;
+    let remaining = loop {
+        let finished: Vec<JoinHandle<()>> = {
+            let mut workers = workers().lock().unwrap();
+            let (done, running) = workers.drain(..).partition(|h| h.is_finished());
+            *workers = running;
+            done
+        };
+        // A worker that panicked has already stopped; nothing else to do.
+        for handle in finished {
+            let _ = handle.join();
+        }
+        let running = workers().lock().unwrap().len();
+        if running == 0 || Instant::now() >= deadline {
+            break running;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    SHUTTING_DOWN.store(false, Ordering::SeqCst);
+    remaining
+}